@@ -0,0 +1,129 @@
+#ifndef THINNING_H
+#define THINNING_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// The result of a call.
+typedef enum ThinningStatus {
+  THINNING_STATUS_OK = 0,
+  // A null pointer, an empty image, a stride smaller than the width or an
+  // index out of range.
+  THINNING_STATUS_INVALID_ARGUMENT = 1,
+  // The progress callback returned false.
+  THINNING_STATUS_CANCELLED = 2,
+  // An internal error; the image contents are unspecified.
+  THINNING_STATUS_PANIC = 3,
+} ThinningStatus;
+
+// A mask of `width` by `height` pixels.
+typedef struct ThinningImage ThinningImage;
+
+// The polylines traced from a skeleton.
+typedef struct ThinningPolylines ThinningPolylines;
+
+// Called after each tile with the iteration, and the number of tiles done
+// and to do in the current pass, and the `user_data` given to the thinning.
+// Returning false cancels the thinning.
+typedef bool (*ThinningProgressFn)(size_t iteration, size_t done, size_t total, void *user_data);
+
+
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Allocates an empty image, returning null if either size is 0.
+struct ThinningImage *thinning_image_new(size_t width, size_t height);
+
+// Frees an image. Does nothing if `image` is null.
+//
+// # Safety
+//
+// `image` must come from `thinning_image_new` and not be used afterwards.
+void thinning_image_free(struct ThinningImage *image);
+
+// The contiguous pixels of the image, `width` bytes per row, which can be
+// filled in directly with 0 and 1 instead of calling `thinning_image_write`.
+//
+// # Safety
+//
+// `image` must be a valid image. The pointer is valid until it's freed.
+uint8_t *thinning_image_data(struct ThinningImage *image);
+
+// Copies the rows of `data`, `stride` bytes apart, into the image, keeping
+// the lowest bit of each pixel, so that e.g. 0/255 masks work too.
+//
+// # Safety
+//
+// `image` must be a valid image, and `data` must hold `height` rows of `stride` bytes.
+enum ThinningStatus thinning_image_write(struct ThinningImage *image,
+                                         const uint8_t *data,
+                                         size_t stride);
+
+// Copies the image into the rows of `data`, `stride` bytes apart, leaving the
+// bytes past the width of each row untouched.
+//
+// # Safety
+//
+// `image` must be a valid image, and `data` must hold `height` rows of `stride` bytes.
+enum ThinningStatus thinning_image_read(const struct ThinningImage *image,
+                                        uint8_t *data,
+                                        size_t stride);
+
+// Thins the image in place, one tile at a time, calling `progress` (if not
+// null) after each tile. When cancelled, the image is left partially thinned.
+//
+// # Safety
+//
+// `image` must be a valid image, and `progress` must be safe to call with `user_data`.
+enum ThinningStatus thinning_zs_tiled(struct ThinningImage *image,
+                                      size_t tile_width,
+                                      size_t tile_height,
+                                      ThinningProgressFn progress,
+                                      void *user_data);
+
+// Traces the thinned image into polylines, stored in `*polylines` on success.
+// `chunk_size` and `max_iter` are usually 10 and 999.
+//
+// # Safety
+//
+// `image` must be a valid image and `polylines` a valid pointer.
+enum ThinningStatus thinning_trace_skeleton(const struct ThinningImage *image,
+                                            size_t chunk_size,
+                                            size_t max_iter,
+                                            struct ThinningPolylines **polylines);
+
+// Frees the traced polylines. Does nothing if `polylines` is null.
+//
+// # Safety
+//
+// `polylines` must come from `thinning_trace_skeleton` and not be used afterwards.
+void thinning_polylines_free(struct ThinningPolylines *polylines);
+
+// The number of polylines.
+//
+// # Safety
+//
+// `polylines` must be valid or null.
+size_t thinning_polylines_count(const struct ThinningPolylines *polylines);
+
+// Points `*points` to the `*len` vertices of the polyline `index`, as
+// interleaved `x, y` pixel coordinates, valid until the polylines are freed.
+//
+// # Safety
+//
+// `polylines` must be valid, and `points` and `len` valid pointers.
+enum ThinningStatus thinning_polyline(const struct ThinningPolylines *polylines,
+                                      size_t index,
+                                      const size_t **points,
+                                      size_t *len);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* THINNING_H */
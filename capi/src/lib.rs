@@ -0,0 +1,313 @@
+use std::{
+    ffi::c_void,
+    panic::{self, AssertUnwindSafe},
+    ptr, slice,
+};
+
+use thinning_core::{region::Region, skeleton};
+
+//================================
+// C API
+//================================
+// Images are opaque handles owning a contiguous mask of one byte per pixel,
+// copied from and to the caller's buffers, which can have any row stride.
+// Only the lowest bit of a pixel is part of the mask. Every function catches
+// panics and reports them with a status code instead of unwinding into C.
+
+/// The result of a call.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ThinningStatus {
+    Ok = 0,
+    /// A null pointer, an empty image, a stride smaller than the width or an
+    /// index out of range.
+    InvalidArgument = 1,
+    /// The progress callback returned false.
+    Cancelled = 2,
+    /// An internal error; the image contents are unspecified.
+    Panic = 3,
+}
+
+/// Called after each tile with the iteration, and the number of tiles done
+/// and to do in the current pass, and the `user_data` given to the thinning.
+/// Returning false cancels the thinning.
+pub type ThinningProgressFn = Option<
+    unsafe extern "C" fn(
+        iteration: usize,
+        done: usize,
+        total: usize,
+        user_data: *mut c_void,
+    ) -> bool,
+>;
+
+/// A mask of `width` by `height` pixels.
+pub struct ThinningImage {
+    data: Vec<u8>,
+    width: usize,
+    height: usize,
+}
+
+/// The polylines traced from a skeleton.
+pub struct ThinningPolylines {
+    polylines: Vec<Vec<[usize; 2]>>,
+}
+
+fn guard(f: impl FnOnce() -> ThinningStatus) -> ThinningStatus {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or(ThinningStatus::Panic)
+}
+
+/// Allocates an empty image, returning null if either size is 0.
+#[no_mangle]
+pub extern "C" fn thinning_image_new(width: usize, height: usize) -> *mut ThinningImage {
+    if width == 0 || height == 0 {
+        return ptr::null_mut();
+    }
+    panic::catch_unwind(|| {
+        Box::into_raw(Box::new(ThinningImage {
+            data: vec![0; width * height],
+            width,
+            height,
+        }))
+    })
+    .unwrap_or(ptr::null_mut())
+}
+
+/// Frees an image. Does nothing if `image` is null.
+///
+/// # Safety
+///
+/// `image` must come from `thinning_image_new` and not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn thinning_image_free(image: *mut ThinningImage) {
+    if !image.is_null() {
+        drop(Box::from_raw(image));
+    }
+}
+
+/// The contiguous pixels of the image, `width` bytes per row, which can be
+/// filled in directly with 0 and 1 instead of calling `thinning_image_write`.
+///
+/// # Safety
+///
+/// `image` must be a valid image. The pointer is valid until it's freed.
+#[no_mangle]
+pub unsafe extern "C" fn thinning_image_data(image: *mut ThinningImage) -> *mut u8 {
+    match image.as_mut() {
+        Some(image) => image.data.as_mut_ptr(),
+        None => ptr::null_mut(),
+    }
+}
+
+/// Copies the rows of `data`, `stride` bytes apart, into the image, keeping
+/// the lowest bit of each pixel, so that e.g. 0/255 masks work too.
+///
+/// # Safety
+///
+/// `image` must be a valid image, and `data` must hold `height` rows of `stride` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn thinning_image_write(
+    image: *mut ThinningImage,
+    data: *const u8,
+    stride: usize,
+) -> ThinningStatus {
+    let image = match image.as_mut() {
+        Some(image) if !data.is_null() && stride >= image.width => image,
+        _ => return ThinningStatus::InvalidArgument,
+    };
+    guard(|| {
+        let width = image.width;
+        for (i, row) in image.data.chunks_exact_mut(width).enumerate() {
+            let src = slice::from_raw_parts(data.add(i * stride), width);
+            for (dst, &src) in row.iter_mut().zip(src) {
+                *dst = src & 1;
+            }
+        }
+        ThinningStatus::Ok
+    })
+}
+
+/// Copies the image into the rows of `data`, `stride` bytes apart, leaving the
+/// bytes past the width of each row untouched.
+///
+/// # Safety
+///
+/// `image` must be a valid image, and `data` must hold `height` rows of `stride` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn thinning_image_read(
+    image: *const ThinningImage,
+    data: *mut u8,
+    stride: usize,
+) -> ThinningStatus {
+    let image = match image.as_ref() {
+        Some(image) if !data.is_null() && stride >= image.width => image,
+        _ => return ThinningStatus::InvalidArgument,
+    };
+    guard(|| {
+        let width = image.width;
+        for (i, row) in image.data.chunks_exact(width).enumerate() {
+            slice::from_raw_parts_mut(data.add(i * stride), width).copy_from_slice(row);
+        }
+        ThinningStatus::Ok
+    })
+}
+
+/// Thins the image in place, one tile at a time, calling `progress` (if not
+/// null) after each tile. When cancelled, the image is left partially thinned.
+///
+/// # Safety
+///
+/// `image` must be a valid image, and `progress` must be safe to call with `user_data`.
+#[no_mangle]
+pub unsafe extern "C" fn thinning_zs_tiled(
+    image: *mut ThinningImage,
+    tile_width: usize,
+    tile_height: usize,
+    progress: ThinningProgressFn,
+    user_data: *mut c_void,
+) -> ThinningStatus {
+    let image = match image.as_mut() {
+        Some(image) if tile_width > 0 && tile_height > 0 => image,
+        _ => return ThinningStatus::InvalidArgument,
+    };
+    guard(|| {
+        let (width, height) = (image.width, image.height);
+        let region = Region::full(width, height);
+        let done = thinning_core::thinning_zs_tiled_progress(
+            &mut image.data,
+            width,
+            height,
+            tile_width,
+            tile_height,
+            &region,
+            |iteration, done, total| match progress {
+                Some(progress) => progress(iteration, done, total, user_data),
+                None => true,
+            },
+        );
+        if done {
+            ThinningStatus::Ok
+        } else {
+            ThinningStatus::Cancelled
+        }
+    })
+}
+
+/// Traces the thinned image into polylines, stored in `*polylines` on success.
+/// `chunk_size` and `max_iter` are usually 10 and 999.
+///
+/// # Safety
+///
+/// `image` must be a valid image and `polylines` a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn thinning_trace_skeleton(
+    image: *const ThinningImage,
+    chunk_size: usize,
+    max_iter: usize,
+    polylines: *mut *mut ThinningPolylines,
+) -> ThinningStatus {
+    let image = match image.as_ref() {
+        Some(image) if !polylines.is_null() && chunk_size > 0 => image,
+        _ => return ThinningStatus::InvalidArgument,
+    };
+    guard(|| {
+        let (width, height) = (image.width, image.height);
+        let traced = skeleton::trace_skeleton(
+            &image.data,
+            width,
+            height,
+            0,
+            0,
+            width,
+            height,
+            chunk_size,
+            max_iter,
+        );
+        *polylines = Box::into_raw(Box::new(ThinningPolylines { polylines: traced }));
+        ThinningStatus::Ok
+    })
+}
+
+/// Frees the traced polylines. Does nothing if `polylines` is null.
+///
+/// # Safety
+///
+/// `polylines` must come from `thinning_trace_skeleton` and not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn thinning_polylines_free(polylines: *mut ThinningPolylines) {
+    if !polylines.is_null() {
+        drop(Box::from_raw(polylines));
+    }
+}
+
+/// The number of polylines.
+///
+/// # Safety
+///
+/// `polylines` must be valid or null.
+#[no_mangle]
+pub unsafe extern "C" fn thinning_polylines_count(polylines: *const ThinningPolylines) -> usize {
+    polylines.as_ref().map_or(0, |p| p.polylines.len())
+}
+
+/// Points `*points` to the `*len` vertices of the polyline `index`, as
+/// interleaved `x, y` pixel coordinates, valid until the polylines are freed.
+///
+/// # Safety
+///
+/// `polylines` must be valid, and `points` and `len` valid pointers.
+#[no_mangle]
+pub unsafe extern "C" fn thinning_polyline(
+    polylines: *const ThinningPolylines,
+    index: usize,
+    points: *mut *const usize,
+    len: *mut usize,
+) -> ThinningStatus {
+    let polyline = match polylines.as_ref() {
+        Some(p) if !points.is_null() && !len.is_null() => match p.polylines.get(index) {
+            Some(polyline) => polyline,
+            None => return ThinningStatus::InvalidArgument,
+        },
+        _ => return ThinningStatus::InvalidArgument,
+    };
+    *points = polyline.as_ptr().cast();
+    *len = polyline.len();
+    ThinningStatus::Ok
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Thins a 9x9 square of `value` in a 13x11 buffer with a stride of 16.
+    fn thin_square(value: u8) -> Vec<u8> {
+        let (width, height, stride) = (13, 11, 16);
+        let mut buffer = vec![0; height * stride];
+        for i in 1..10 {
+            buffer[i * stride + 2..i * stride + 11].fill(value);
+        }
+        unsafe {
+            let image = thinning_image_new(width, height);
+            assert_eq!(
+                thinning_image_write(image, buffer.as_ptr(), stride),
+                ThinningStatus::Ok
+            );
+            assert_eq!(
+                thinning_zs_tiled(image, 4, 4, None, ptr::null_mut()),
+                ThinningStatus::Ok
+            );
+            assert_eq!(
+                thinning_image_read(image, buffer.as_mut_ptr(), stride),
+                ThinningStatus::Ok
+            );
+            thinning_image_free(image);
+        }
+        buffer
+    }
+
+    #[test]
+    fn thins_0_255_masks() {
+        let expected = thin_square(1);
+        assert!(expected.contains(&1));
+        assert_eq!(thin_square(255), expected);
+    }
+}
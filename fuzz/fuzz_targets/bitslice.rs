@@ -0,0 +1,69 @@
+#![no_main]
+
+use libfuzzer_sys::{
+    arbitrary::{self, Arbitrary, Unstructured},
+    fuzz_target,
+};
+use thinning::iteration;
+
+/// An image up to 320 pixels wide, wider than the 256 pixels of the AVX2
+/// lanes, with some pixels already marked, and a window of it.
+#[derive(Debug)]
+struct Input {
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+    window: (usize, usize, usize, usize),
+    iter: i32,
+}
+
+impl<'a> Arbitrary<'a> for Input {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        let width = u.int_in_range(1..=320)?;
+        let height = u.int_in_range(1..=8)?;
+        let mut pixels = vec![0; width * height];
+        for p in &mut pixels {
+            *p = u.arbitrary::<u8>()? & 3;
+        }
+        let win_x = u.int_in_range(0..=width - 1)?;
+        let win_y = u.int_in_range(0..=height - 1)?;
+        let win_w = u.int_in_range(1..=width - win_x)?;
+        let win_h = u.int_in_range(1..=height - win_y)?;
+        Ok(Self {
+            width,
+            height,
+            pixels,
+            window: (win_x, win_y, win_w, win_h),
+            iter: u.arbitrary::<bool>()? as i32,
+        })
+    }
+}
+
+fuzz_target!(|input: Input| {
+    let Input {
+        width,
+        height,
+        pixels,
+        window: (win_x, win_y, win_w, win_h),
+        iter,
+    } = input;
+
+    let mut expected = pixels.clone();
+    let expected_diff = iteration::scalar(
+        &mut expected,
+        win_x,
+        win_y,
+        win_w,
+        win_h,
+        width,
+        height,
+        iter,
+    );
+    // the widest implementation supported by the CPU, then the portable one
+    for bitsliced in [iteration::bitsliced, iteration::bitsliced_u64] {
+        let mut actual = pixels.clone();
+        let diff = bitsliced(&mut actual, win_x, win_y, win_w, win_h, width, height, iter);
+        assert_eq!(actual, expected);
+        assert_eq!(diff, expected_diff);
+    }
+});
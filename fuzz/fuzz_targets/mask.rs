@@ -0,0 +1,35 @@
+use libfuzzer_sys::arbitrary::{self, Arbitrary, Unstructured};
+
+/// A mask of 1 to 32 pixels on each side, one byte per pixel, 0 or 1. Short
+/// inputs give 1-pixel wide and empty images.
+#[derive(Debug)]
+pub struct Mask {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+impl<'a> Arbitrary<'a> for Mask {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        let width = u.int_in_range(1..=32)?;
+        let height = u.int_in_range(1..=32)?;
+        let mut pixels = vec![0; width * height];
+        for p in &mut pixels {
+            *p = u.arbitrary::<bool>()? as u8;
+        }
+        Ok(Self {
+            width,
+            height,
+            pixels,
+        })
+    }
+}
+
+/// Checks that the thinning only removed pixels, without leaving any markers.
+#[allow(dead_code)]
+pub fn check_thinned(mask: &[u8], thinned: &[u8]) {
+    for (k, (&before, &after)) in mask.iter().zip(thinned).enumerate() {
+        assert!(after <= 1, "marker left at pixel {k}");
+        assert!(after <= before, "pixel {k} added");
+    }
+}
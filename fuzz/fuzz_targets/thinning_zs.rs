@@ -0,0 +1,23 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+mod mask;
+
+use mask::Mask;
+
+fuzz_target!(|mask: Mask| {
+    let Mask {
+        width,
+        height,
+        pixels,
+    } = mask;
+    let mut thinned = pixels.clone();
+    thinning::thinning_zs(&mut thinned, width, height);
+    mask::check_thinned(&pixels, &thinned);
+
+    // the skeleton is already thin
+    let mut again = thinned.clone();
+    thinning::thinning_zs(&mut again, width, height);
+    assert_eq!(again, thinned);
+});
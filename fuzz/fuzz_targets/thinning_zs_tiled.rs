@@ -0,0 +1,52 @@
+#![no_main]
+
+use libfuzzer_sys::{
+    arbitrary::{self, Arbitrary},
+    fuzz_target,
+};
+use thinning::region::{Border, Region};
+
+mod mask;
+
+use mask::Mask;
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    mask: Mask,
+    tile_width: u8,
+    tile_height: u8,
+    /// Replicate the pixels on the edges instead of seeing background past them.
+    replicate: bool,
+}
+
+fuzz_target!(|input: Input| {
+    let Mask {
+        width,
+        height,
+        pixels,
+    } = input.mask;
+    let tile_width = 1 + input.tile_width as usize % 16;
+    let tile_height = 1 + input.tile_height as usize % 16;
+    let border = if input.replicate {
+        Border::Replicate
+    } else {
+        Border::Background
+    };
+    let region = Region::full(width, height).with_border(border);
+
+    let mut thinned = pixels.clone();
+    thinning::thinning_zs_tiled(
+        &mut thinned,
+        width,
+        height,
+        tile_width,
+        tile_height,
+        &region,
+    );
+    mask::check_thinned(&pixels, &thinned);
+
+    // the tiles don't change the result
+    let mut whole = pixels.clone();
+    thinning::thinning_zs_tiled(&mut whole, width, height, width, height, &region);
+    assert_eq!(thinned, whole);
+});
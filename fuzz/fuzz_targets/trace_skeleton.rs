@@ -0,0 +1,50 @@
+#![no_main]
+
+use libfuzzer_sys::{
+    arbitrary::{self, Arbitrary},
+    fuzz_target,
+};
+use thinning::{region::Region, skeleton};
+
+mod mask;
+
+use mask::Mask;
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    mask: Mask,
+    /// Trace the skeleton of the mask instead of the mask itself.
+    thin: bool,
+    chunk: [u8; 4],
+    chunk_size: u8,
+    max_iter: u8,
+}
+
+fuzz_target!(|input: Input| {
+    let Mask {
+        width,
+        height,
+        mut pixels,
+    } = input.mask;
+    if input.thin {
+        let region = Region::full(width, height);
+        thinning::thinning_zs_tiled(&mut pixels, width, height, 8, 8, &region);
+    }
+    let [x, y, w, h] = input.chunk.map(|v| v as usize);
+    let (x, y) = (x % width, y % height);
+    let (w, h) = (1 + w % (width - x), 1 + h % (height - y));
+    let chunk_size = 1 + input.chunk_size as usize % 16;
+    let max_iter = input.max_iter as usize % 32;
+
+    let polylines =
+        skeleton::trace_skeleton(&pixels, width, height, x, y, w, h, chunk_size, max_iter);
+    for polyline in &polylines {
+        assert!(polyline.len() >= 2, "{polyline:?}");
+        for &[px, py] in polyline {
+            assert!(
+                (x..x + w).contains(&px) && (y..y + h).contains(&py),
+                "vertex {px}, {py} outside of the chunk"
+            );
+        }
+    }
+});
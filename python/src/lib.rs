@@ -0,0 +1,253 @@
+use std::{error::Error, fs};
+
+use gdal::{Dataset, DatasetOptions, GdalOpenFlags};
+use gdal_sys::{
+    GDALDataType,
+    GDALRWFlag::{GF_Read, GF_Write},
+};
+use numpy::{
+    ndarray::Array2, IntoPyArray, PyArray2, PyReadonlyArray2, PyReadwriteArray2,
+    PyUntypedArrayMethods,
+};
+use pyo3::{
+    exceptions::{PyIOError, PyValueError},
+    prelude::*,
+};
+use thinning_core::{frontier, prune, raster::VirtualMem, region::Region, skeleton};
+
+//================================
+// PYTHON BINDINGS
+//================================
+// The arrays are `uint8` masks of shape `(height, width)`, processed in place
+// without copying, so they must be C-contiguous and writeable. Like in the
+// binary, only the lowest bit of a pixel is part of the mask; the arrays are
+// reduced to it before thinning, so e.g. a 0/255 mask comes back as 0/1, like
+// the copy made by `thin_file`. The tracing reads only that bit too. The
+// GIL is released while the thinning runs. Invalid arguments raise a
+// `ValueError` instead of panicking in the core.
+
+fn check_size(width: usize, height: usize) -> PyResult<()> {
+    if width == 0 || height == 0 {
+        return Err(PyValueError::new_err("the array must not be empty"));
+    }
+    Ok(())
+}
+
+/// The pixels of the array, keeping only their lowest bit.
+fn as_mask<'a>(array: &'a mut PyReadwriteArray2<u8>) -> PyResult<(&'a mut [u8], usize, usize)> {
+    let (height, width) = (array.shape()[0], array.shape()[1]);
+    check_size(width, height)?;
+    let im = array
+        .as_slice_mut()
+        .map_err(|_| PyValueError::new_err("the array must be C-contiguous"))?;
+    for p in im.iter_mut() {
+        *p &= 1;
+    }
+    Ok((im, width, height))
+}
+
+/// Polylines as a list of `(n, 2)` arrays of `(x, y)` pixel coordinates.
+fn polylines_to_py(
+    py: Python<'_>,
+    polylines: Vec<Vec<[usize; 2]>>,
+) -> Vec<Bound<'_, PyArray2<u64>>> {
+    polylines
+        .into_iter()
+        .map(|polyline| {
+            let n = polyline.len();
+            let points = polyline
+                .into_iter()
+                .flat_map(|[x, y]| [x as u64, y as u64])
+                .collect();
+            Array2::from_shape_vec((n, 2), points)
+                .unwrap()
+                .into_pyarray(py)
+        })
+        .collect()
+}
+
+fn thin(
+    im: &mut [u8],
+    width: usize,
+    height: usize,
+    tile_width: usize,
+    tile_height: usize,
+    use_frontier: bool,
+    max_spur_length: Option<usize>,
+) {
+    let region = Region::full(width, height);
+    if use_frontier {
+        frontier::thinning_zs_frontier(im, width, height, tile_width, tile_height, &region);
+    } else {
+        thinning_core::thinning_zs_tiled(im, width, height, tile_width, tile_height, &region);
+    }
+    if let Some(max_length) = max_spur_length {
+        prune::prune_spurs(
+            im,
+            width,
+            height,
+            tile_width,
+            tile_height,
+            &region,
+            max_length,
+        );
+    }
+}
+
+/// Thins the mask in place, rescanning the whole image on each iteration.
+#[pyfunction]
+fn thinning_zs(py: Python<'_>, mut array: PyReadwriteArray2<u8>) -> PyResult<()> {
+    let (im, width, height) = as_mask(&mut array)?;
+    py.detach(|| thinning_core::thinning_zs(im, width, height));
+    Ok(())
+}
+
+/// Thins the mask in place, one tile at a time.
+#[pyfunction]
+#[pyo3(signature = (array, tile_width = 512, tile_height = 512, frontier = false))]
+fn thinning_zs_tiled(
+    py: Python<'_>,
+    mut array: PyReadwriteArray2<u8>,
+    tile_width: usize,
+    tile_height: usize,
+    frontier: bool,
+) -> PyResult<()> {
+    if tile_width == 0 || tile_height == 0 {
+        return Err(PyValueError::new_err("the tile size must be positive"));
+    }
+    let (im, width, height) = as_mask(&mut array)?;
+    py.detach(|| thin(im, width, height, tile_width, tile_height, frontier, None));
+    Ok(())
+}
+
+/// Removes in place the branches of a thinned mask going from an endpoint to a
+/// junction in at most `max_length` pixels. Returns the number of removed branches.
+#[pyfunction]
+fn prune_spurs(
+    py: Python<'_>,
+    mut array: PyReadwriteArray2<u8>,
+    max_length: usize,
+) -> PyResult<usize> {
+    let (im, width, height) = as_mask(&mut array)?;
+    let region = Region::full(width, height);
+    Ok(py.detach(|| prune::prune_spurs(im, width, height, width, height, &region, max_length)))
+}
+
+/// Traces a thinned mask into polylines.
+#[pyfunction]
+#[pyo3(signature = (array, chunk_size = 10, max_iter = 999))]
+fn trace_skeleton<'py>(
+    py: Python<'py>,
+    array: PyReadonlyArray2<'py, u8>,
+    chunk_size: usize,
+    max_iter: usize,
+) -> PyResult<Vec<Bound<'py, PyArray2<u64>>>> {
+    let (height, width) = (array.shape()[0], array.shape()[1]);
+    check_size(width, height)?;
+    if chunk_size == 0 {
+        return Err(PyValueError::new_err("the chunk size must be positive"));
+    }
+    let im = array
+        .as_slice()
+        .map_err(|_| PyValueError::new_err("the array must be C-contiguous"))?;
+    let polylines = py.detach(|| {
+        skeleton::trace_skeleton_par(im, width, height, 0, 0, width, height, chunk_size, max_iter)
+    });
+    Ok(polylines_to_py(py, polylines))
+}
+
+fn thin_file_impl(
+    input: &str,
+    output: &str,
+    use_frontier: bool,
+    max_spur_length: Option<usize>,
+) -> Result<(), Box<dyn Error>> {
+    fs::copy(input, output)?;
+    let ds = Dataset::open_ex(
+        output,
+        DatasetOptions {
+            open_flags: GdalOpenFlags::GDAL_OF_UPDATE,
+            ..DatasetOptions::default()
+        },
+    )?;
+    let band = ds.rasterband(1)?;
+    if band.band_type() != GDALDataType::GDT_Byte {
+        return Err("only Byte images are supported".into());
+    }
+    let (width, height) = band.size();
+    let (tile_width, tile_height) = band.block_size();
+    let mut mem = VirtualMem::new(&band, GF_Write)?;
+    let im = mem.as_mut_slice::<u8>();
+    for p in im.iter_mut() {
+        *p &= 1;
+    }
+    thin(
+        im,
+        width,
+        height,
+        tile_width,
+        tile_height,
+        use_frontier,
+        max_spur_length,
+    );
+    Ok(())
+}
+
+fn trace_file_impl(
+    path: &str,
+    chunk_size: usize,
+    max_iter: usize,
+) -> Result<Vec<Vec<[usize; 2]>>, Box<dyn Error>> {
+    let ds = Dataset::open(path)?;
+    let band = ds.rasterband(1)?;
+    if band.band_type() != GDALDataType::GDT_Byte {
+        return Err("only Byte images are supported".into());
+    }
+    let (width, height) = band.size();
+    let mut mem = VirtualMem::new(&band, GF_Read)?;
+    let im = mem.as_mut_slice::<u8>();
+    Ok(skeleton::trace_skeleton_par(
+        im, width, height, 0, 0, width, height, chunk_size, max_iter,
+    ))
+}
+
+/// Copies the Byte raster `input` to `output` and thins it there, leaving the input alone.
+/// `input` must be a single-file format like GeoTIFF.
+#[pyfunction]
+#[pyo3(signature = (input, output, frontier = false, prune = None))]
+fn thin_file(
+    py: Python<'_>,
+    input: &str,
+    output: &str,
+    frontier: bool,
+    prune: Option<usize>,
+) -> PyResult<()> {
+    py.detach(|| thin_file_impl(input, output, frontier, prune).map_err(|e| e.to_string()))
+        .map_err(PyIOError::new_err)
+}
+
+/// Traces the thinned Byte raster `path` into polylines.
+#[pyfunction]
+#[pyo3(signature = (path, chunk_size = 10, max_iter = 999))]
+fn trace_file<'py>(
+    py: Python<'py>,
+    path: &str,
+    chunk_size: usize,
+    max_iter: usize,
+) -> PyResult<Vec<Bound<'py, PyArray2<u64>>>> {
+    let polylines = py
+        .detach(|| trace_file_impl(path, chunk_size, max_iter).map_err(|e| e.to_string()))
+        .map_err(PyIOError::new_err)?;
+    Ok(polylines_to_py(py, polylines))
+}
+
+#[pymodule]
+fn thinning(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(thinning_zs, m)?)?;
+    m.add_function(wrap_pyfunction!(thinning_zs_tiled, m)?)?;
+    m.add_function(wrap_pyfunction!(prune_spurs, m)?)?;
+    m.add_function(wrap_pyfunction!(trace_skeleton, m)?)?;
+    m.add_function(wrap_pyfunction!(thin_file, m)?)?;
+    m.add_function(wrap_pyfunction!(trace_file, m)?)?;
+    Ok(())
+}
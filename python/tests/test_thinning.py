@@ -0,0 +1,70 @@
+import numpy as np
+import pytest
+
+import thinning
+
+
+def cross(value):
+    """A thick cross with a short spur, its pixels set to `value`."""
+    mask = np.zeros((15, 15), dtype=np.uint8)
+    mask[6:9, 1:14] = value
+    mask[1:14, 6:9] = value
+    mask[2:4, 10] = value
+    return mask
+
+
+@pytest.mark.parametrize(
+    "thin",
+    [
+        thinning.thinning_zs,
+        lambda mask: thinning.thinning_zs_tiled(mask, 4, 4),
+        lambda mask: thinning.thinning_zs_tiled(mask, 4, 4, frontier=True),
+        lambda mask: thinning.prune_spurs(mask, 3),
+    ],
+)
+def test_0_255_mask(thin):
+    expected = cross(1)
+    thin(expected)
+    assert expected.any()
+
+    mask = cross(255)
+    thin(mask)
+    np.testing.assert_array_equal(mask, expected)
+
+
+def test_trace_0_255_mask():
+    mask = cross(1)
+    thinning.thinning_zs(mask)
+    expected = thinning.trace_skeleton(mask)
+    assert expected
+
+    actual = thinning.trace_skeleton(mask * 255)
+    assert len(actual) == len(expected)
+    for a, e in zip(actual, expected):
+        np.testing.assert_array_equal(a, e)
+
+
+@pytest.mark.parametrize(
+    "call",
+    [
+        thinning.thinning_zs,
+        thinning.thinning_zs_tiled,
+        lambda mask: thinning.prune_spurs(mask, 3),
+        thinning.trace_skeleton,
+    ],
+)
+@pytest.mark.parametrize("shape", [(0, 5), (5, 0), (0, 0)])
+def test_empty_array(call, shape):
+    with pytest.raises(ValueError):
+        call(np.zeros(shape, dtype=np.uint8))
+
+
+@pytest.mark.parametrize("tile_size", [(0, 4), (4, 0)])
+def test_zero_tile_size(tile_size):
+    with pytest.raises(ValueError):
+        thinning.thinning_zs_tiled(cross(1), *tile_size)
+
+
+def test_zero_chunk_size():
+    with pytest.raises(ValueError):
+        thinning.trace_skeleton(cross(1), chunk_size=0)
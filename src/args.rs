@@ -0,0 +1,326 @@
+use std::{error::Error, str::FromStr};
+
+use thinning::{
+    geo::PixelAnchor,
+    label::Connectivity,
+    morphology::Step,
+    multiclass::ClassSelection,
+    region::{BorderSpec, RegionSpec},
+    skeleton::Tracer,
+};
+
+pub struct Args {
+    pub input: String,
+    /// Band of the input to process, starting from 1.
+    pub band: isize,
+    /// Treat the band as class labels and skeletonise these classes separately.
+    pub split_classes: Option<ClassSelection>,
+    /// Raster to write the skeletons of the classes to.
+    pub class_skeletons: Option<String>,
+    /// Write one band per class instead of a single band with the class values.
+    pub class_bands: bool,
+    /// Morphological operations to apply before thinning, in order.
+    pub morphology: Vec<Step>,
+    /// Remove the foreground components smaller than this many pixels before thinning.
+    pub min_area: Option<u64>,
+    /// Fill the holes smaller than this many pixels before thinning.
+    pub fill_holes: Option<u64>,
+    /// Use the frontier-based thinning instead of rescanning whole tiles.
+    pub frontier: bool,
+    /// Only thin and trace this part of the raster.
+    pub region: Option<RegionSpec>,
+    /// How the thinning sees the pixels past the edges of the raster.
+    pub border: BorderSpec,
+    /// Skeleton from a previous run, only thinning again the components touched by the edits.
+    pub previous: Option<String>,
+    /// Raster marking the edited pixels for `previous`, instead of `region`.
+    pub changed: Option<String>,
+    /// Remove the branches from an endpoint to a junction of at most this many pixels.
+    pub prune: Option<usize>,
+    /// Label raster of the mask components, also used for the skeletons.
+    pub labels: Option<String>,
+    /// CSV table with the statistics of each component.
+    pub components: Option<String>,
+    pub connectivity: Connectivity,
+    /// Raster with the class of each skeleton pixel.
+    pub classes: Option<String>,
+    /// Vector file to write the endpoints and junctions to.
+    pub nodes: Option<String>,
+    /// Vector file to write the traced skeleton to.
+    pub trace: Option<String>,
+    pub tracer: Tracer,
+    /// Radius of the neighbourhood used to move the traced vertices to sub-pixel positions.
+    pub refine: Option<usize>,
+    /// Write the closed loops of the skeleton as polygons.
+    pub ring_polygons: bool,
+    /// Raster to burn the traced polylines into, to compare them with the skeleton.
+    pub rasterize: Option<String>,
+    /// Width in pixels of the buffer burnt around all the polylines, instead of
+    /// a single pixel.
+    pub burn_width: Option<f64>,
+    /// CSV table with the skeleton pixels missed and added by the burnt polylines.
+    pub compare: Option<String>,
+    /// Write the vector outputs in map coordinates instead of pixel coordinates.
+    pub georef: bool,
+    pub pixel_anchor: PixelAnchor,
+    /// CRS to reproject the vector outputs to, implies `georef`.
+    pub target_srs: Option<String>,
+}
+
+impl Args {
+    pub fn parse() -> Result<Self, Box<dyn Error>> {
+        let mut input = None;
+        let mut band = 1;
+        let mut split_classes = None;
+        let mut class_skeletons = None;
+        let mut class_bands = false;
+        let mut morphology = Vec::new();
+        let mut min_area = None;
+        let mut fill_holes = None;
+        let mut frontier = false;
+        let mut region = None;
+        let mut border = None;
+        let mut previous = None;
+        let mut changed = None;
+        let mut prune = None;
+        let mut labels = None;
+        let mut components = None;
+        let mut connectivity = Connectivity::Eight;
+        let mut classes = None;
+        let mut nodes = None;
+        let mut trace = None;
+        let mut tracer = None;
+        let mut refine = None;
+        let mut ring_polygons = false;
+        let mut rasterize = None;
+        let mut burn_width = None;
+        let mut compare = None;
+        let mut georef = false;
+        let mut pixel_anchor = None;
+        let mut target_srs = None;
+
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--band" => band = parse_value(&mut args, &arg)?,
+                "--split-classes" => split_classes = Some(parse_value(&mut args, &arg)?),
+                "--class-skeletons" => class_skeletons = Some(value(&mut args, &arg)?),
+                "--class-bands" => class_bands = true,
+                "--morphology" => morphology.push(parse_value(&mut args, &arg)?),
+                "--min-area" => min_area = Some(parse_value(&mut args, &arg)?),
+                "--fill-holes" => fill_holes = Some(parse_value(&mut args, &arg)?),
+                "--frontier" => frontier = true,
+                "--window" | "--bbox" | "--cutline" => {
+                    if region.is_some() {
+                        return Err("only one of --window, --bbox and --cutline can be used".into());
+                    }
+                    let value = value(&mut args, &arg)?;
+                    region = Some(match arg.as_str() {
+                        "--window" => RegionSpec::parse_window(&value)?,
+                        "--bbox" => RegionSpec::parse_bbox(&value)?,
+                        _ => RegionSpec::Cutline(value),
+                    });
+                }
+                "--border" | "--overlap" => {
+                    if border.is_some() {
+                        return Err("only one of --border and --overlap can be used".into());
+                    }
+                    let value = value(&mut args, &arg)?;
+                    border = Some(match (arg.as_str(), value.as_str()) {
+                        ("--border", "background") => BorderSpec::Background,
+                        ("--border", "replicate") => BorderSpec::Replicate,
+                        ("--border", b) => {
+                            return Err(format!(
+                                "invalid border {b}, expected background or replicate"
+                            )
+                            .into())
+                        }
+                        _ => BorderSpec::Overlap(value),
+                    });
+                }
+                "--previous" => previous = Some(value(&mut args, &arg)?),
+                "--changed" => changed = Some(value(&mut args, &arg)?),
+                "--prune" => prune = Some(parse_value(&mut args, &arg)?),
+                "--labels" => labels = Some(value(&mut args, &arg)?),
+                "--components" => components = Some(value(&mut args, &arg)?),
+                "--connectivity" => {
+                    connectivity = match parse_value::<u8>(&mut args, &arg)? {
+                        4 => Connectivity::Four,
+                        8 => Connectivity::Eight,
+                        c => {
+                            return Err(format!("invalid connectivity {c}, expected 4 or 8").into())
+                        }
+                    }
+                }
+                "--classes" => classes = Some(value(&mut args, &arg)?),
+                "--nodes" => nodes = Some(value(&mut args, &arg)?),
+                "--trace" => trace = Some(value(&mut args, &arg)?),
+                "--tracer" => {
+                    tracer = Some(match value(&mut args, &arg)?.as_str() {
+                        "heuristic" => Tracer::Heuristic,
+                        "exact" => Tracer::Exact,
+                        t => {
+                            return Err(
+                                format!("invalid tracer {t}, expected heuristic or exact").into()
+                            )
+                        }
+                    })
+                }
+                "--refine" => refine = Some(parse_value(&mut args, &arg)?),
+                "--ring-polygons" => ring_polygons = true,
+                "--rasterize" => rasterize = Some(value(&mut args, &arg)?),
+                "--burn-width" => burn_width = Some(parse_value(&mut args, &arg)?),
+                "--compare" => compare = Some(value(&mut args, &arg)?),
+                "--georef" => georef = true,
+                "--pixel-anchor" => {
+                    pixel_anchor = Some(match value(&mut args, &arg)?.as_str() {
+                        "center" => PixelAnchor::Center,
+                        "corner" => PixelAnchor::Corner,
+                        a => {
+                            return Err(format!(
+                                "invalid pixel anchor {a}, expected center or corner"
+                            )
+                            .into())
+                        }
+                    })
+                }
+                "--t-srs" => {
+                    target_srs = Some(value(&mut args, &arg)?);
+                    georef = true;
+                }
+                _ if arg.starts_with("--") => return Err(format!("unknown option {arg}").into()),
+                _ if input.is_none() => input = Some(arg),
+                _ => return Err(format!("unexpected argument {arg}").into()),
+            }
+        }
+
+        if band < 1 {
+            return Err(format!("invalid band {band}, the bands start from 1").into());
+        }
+        if split_classes.is_some() != class_skeletons.is_some() {
+            return Err("--split-classes and --class-skeletons must be used together".into());
+        }
+        if class_bands && split_classes.is_none() {
+            return Err("--class-bands requires --split-classes".into());
+        }
+        if split_classes.is_some() {
+            // the other steps work on a single mask, which is overwritten
+            let unsupported = [
+                ("--morphology", !morphology.is_empty()),
+                ("--min-area", min_area.is_some()),
+                ("--fill-holes", fill_holes.is_some()),
+                ("--previous", previous.is_some()),
+                ("--labels", labels.is_some()),
+                ("--classes", classes.is_some()),
+                ("--nodes", nodes.is_some()),
+                ("--trace", trace.is_some()),
+                ("--overlap", matches!(border, Some(BorderSpec::Overlap(_)))),
+            ];
+            if let Some((name, _)) = unsupported.iter().find(|(_, used)| *used) {
+                return Err(format!("--split-classes can't be combined with {name}").into());
+            }
+        }
+        if ring_polygons && trace.is_none() {
+            return Err("--ring-polygons requires --trace".into());
+        }
+        if tracer.is_some() && trace.is_none() {
+            return Err("--tracer requires --trace".into());
+        }
+        if refine.is_some() && trace.is_none() {
+            return Err("--refine requires --trace".into());
+        }
+        // --t-srs implies --georef, so it's checked first
+        let georef_options = [
+            ("--t-srs", target_srs.is_some()),
+            ("--georef", georef),
+            ("--pixel-anchor", pixel_anchor.is_some()),
+        ];
+        if trace.is_none() && nodes.is_none() {
+            if let Some((name, _)) = georef_options.iter().find(|(_, used)| *used) {
+                return Err(format!("{name} requires --trace or --nodes").into());
+            }
+        }
+        if rasterize.is_some() && trace.is_none() {
+            return Err("--rasterize requires --trace".into());
+        }
+        if burn_width.is_some() && rasterize.is_none() {
+            return Err("--burn-width requires --rasterize".into());
+        }
+        if compare.is_some() && rasterize.is_none() {
+            return Err("--compare requires --rasterize".into());
+        }
+        if components.is_some() && labels.is_none() {
+            return Err("--components requires --labels".into());
+        }
+        if changed.is_some() && previous.is_none() {
+            return Err("--changed requires --previous".into());
+        }
+        if previous.is_some() {
+            if changed.is_none() && region.is_none() {
+                return Err(
+                    "--previous requires --changed, --window, --bbox or --cutline for the edits"
+                        .into(),
+                );
+            }
+            if !morphology.is_empty() {
+                return Err("--previous can't be combined with --morphology".into());
+            }
+            // the copied skeleton would be pruned again
+            if prune.is_some() {
+                return Err("--previous can't be combined with --prune".into());
+            }
+            // the frontier would start from every pixel, not from the edits
+            if frontier {
+                return Err("--previous can't be combined with --frontier".into());
+            }
+        }
+
+        Ok(Self {
+            input: input.ok_or("missing input image")?,
+            band,
+            split_classes,
+            class_skeletons,
+            class_bands,
+            morphology,
+            min_area,
+            fill_holes,
+            frontier,
+            region,
+            border: border.unwrap_or(BorderSpec::Background),
+            previous,
+            changed,
+            prune,
+            labels,
+            components,
+            connectivity,
+            classes,
+            nodes,
+            trace,
+            tracer: tracer.unwrap_or(Tracer::Heuristic),
+            refine,
+            ring_polygons,
+            rasterize,
+            burn_width,
+            compare,
+            georef,
+            pixel_anchor: pixel_anchor.unwrap_or(PixelAnchor::Center),
+            target_srs,
+        })
+    }
+}
+
+fn value(args: &mut impl Iterator<Item = String>, name: &str) -> Result<String, Box<dyn Error>> {
+    Ok(args
+        .next()
+        .ok_or_else(|| format!("missing value for {name}"))?)
+}
+
+fn parse_value<T: FromStr>(
+    args: &mut impl Iterator<Item = String>,
+    name: &str,
+) -> Result<T, Box<dyn Error>> {
+    let value = value(args, name)?;
+    value
+        .parse()
+        .map_err(|_| format!("invalid value {value} for {name}").into())
+}
@@ -0,0 +1,358 @@
+use std::ops::{BitAnd, BitOr, BitXor, Not};
+
+//================================
+// BIT-SLICED ZHANG-SUEN
+//================================
+// Same as `thinning_zs_iteration`, but working on 64 pixels of a row at a time.
+// The mask bits of a row segment and of the rows above and below are packed
+// into u64 words, the neighbours being the same words shifted by one pixel,
+// and the conditions on the crossing number and on the neighbour count are
+// evaluated with boolean logic on the words. Pixels are marked in the second
+// bit exactly like the scalar version, so the results are identical.
+// With AVX2, four words (256 pixels) are processed together and the bits are
+// packed with `movemask`; otherwise a single u64 is used.
+
+#[derive(Clone, Copy)]
+struct Lanes<const N: usize>([u64; N]);
+
+impl<const N: usize> Lanes<N> {
+    #[inline(always)]
+    fn any(self) -> bool {
+        self.0.iter().any(|&l| l != 0)
+    }
+}
+
+macro_rules! lanes_op {
+    ($trait:ident, $fn:ident, $op:tt) => {
+        impl<const N: usize> $trait for Lanes<N> {
+            type Output = Self;
+
+            #[inline(always)]
+            fn $fn(self, rhs: Self) -> Self {
+                let mut out = self.0;
+                for k in 0..N {
+                    out[k] = self.0[k] $op rhs.0[k];
+                }
+                Lanes(out)
+            }
+        }
+    };
+}
+
+lanes_op!(BitAnd, bitand, &);
+lanes_op!(BitOr, bitor, |);
+lanes_op!(BitXor, bitxor, ^);
+
+impl<const N: usize> Not for Lanes<N> {
+    type Output = Self;
+
+    #[inline(always)]
+    fn not(self) -> Self {
+        let mut out = self.0;
+        for l in &mut out {
+            *l = !*l;
+        }
+        Lanes(out)
+    }
+}
+
+#[inline(always)]
+fn pack_scalar(bytes: &[u8]) -> u64 {
+    let mut bits = 0;
+    let mut chunks = bytes.chunks_exact(8);
+    for (k, chunk) in (&mut chunks).enumerate() {
+        // gather the low bit of each byte into the top byte
+        let v = u64::from_le_bytes(chunk.try_into().unwrap()) & 0x0101_0101_0101_0101;
+        bits |= (v.wrapping_mul(0x0102_0408_1020_4080) >> 56) << (8 * k);
+    }
+    let offset = bytes.len() - chunks.remainder().len();
+    for (k, &b) in chunks.remainder().iter().enumerate() {
+        bits |= ((b & 1) as u64) << (offset + k);
+    }
+    bits
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn pack_avx2(bytes: &[u8]) -> u64 {
+    use std::arch::x86_64::*;
+
+    if bytes.len() < 64 {
+        return pack_scalar(bytes);
+    }
+    // move the mask bit to the top of each byte, where movemask picks it up
+    let lo = _mm256_loadu_si256(bytes.as_ptr() as *const __m256i);
+    let hi = _mm256_loadu_si256(bytes.as_ptr().add(32) as *const __m256i);
+    let lo = _mm256_movemask_epi8(_mm256_slli_epi16(lo, 7)) as u32 as u64;
+    let hi = _mm256_movemask_epi8(_mm256_slli_epi16(hi, 7)) as u32 as u64;
+    lo | hi << 32
+}
+
+/// Loads the pixels `j0..j0 + n` (`n <= 64`) of row `r` and their left and right neighbours.
+#[inline(always)]
+fn load_word<const AVX2: bool>(im: &[u8], w: usize, r: usize, j0: usize, n: usize) -> [u64; 3] {
+    if n == 0 {
+        return [0; 3];
+    }
+    let bytes = &im[r * w + j0..r * w + j0 + n];
+    let center = if AVX2 {
+        #[cfg(target_arch = "x86_64")]
+        {
+            unsafe { pack_avx2(bytes) }
+        }
+        #[cfg(not(target_arch = "x86_64"))]
+        {
+            pack_scalar(bytes)
+        }
+    } else {
+        pack_scalar(bytes)
+    };
+    let mask = if n == 64 { !0 } else { (1 << n) - 1 };
+    let left = ((center << 1) | (im[r * w + j0 - 1] & 1) as u64) & mask;
+    let right = (center >> 1) | ((im[r * w + j0 + n] & 1) as u64) << (n - 1);
+    [left, center, right]
+}
+
+#[inline(always)]
+fn load_row<const N: usize, const AVX2: bool>(
+    im: &[u8],
+    w: usize,
+    r: usize,
+    j0: usize,
+    max_x: usize,
+) -> [Lanes<N>; 3] {
+    let mut row = [Lanes([0; N]); 3];
+    for k in 0..N {
+        let j = j0 + 64 * k;
+        let n = max_x.saturating_sub(j).min(64);
+        let [left, center, right] = load_word::<AVX2>(im, w, r, j, n);
+        row[0].0[k] = left;
+        row[1].0[k] = center;
+        row[2].0[k] = right;
+    }
+    row
+}
+
+/// Returns the pixels to remove among `p1`, given its neighbours `p2..p9`
+/// in the same order as in `thinning_zs_iteration`.
+#[inline(always)]
+fn removable<const N: usize>(p: [Lanes<N>; 9], iter: i32) -> Lanes<N> {
+    let [p1, p2, p3, p4, p5, p6, p7, p8, p9] = p;
+    let zero = Lanes([0; N]);
+
+    // a == 1: exactly one 0 -> 1 transition around the pixel
+    let transitions = [
+        !p2 & p3,
+        !p3 & p4,
+        !p4 & p5,
+        !p5 & p6,
+        !p6 & p7,
+        !p7 & p8,
+        !p8 & p9,
+        !p9 & p2,
+    ];
+    let mut one = zero;
+    let mut two = zero;
+    for t in transitions {
+        two = two | (one & t);
+        one = one | t;
+    }
+    let a_is_1 = one & !two;
+
+    // 2 <= b <= 6, counting the neighbours with a bit-sliced adder
+    let (mut c0, mut c1, mut c2, mut c3) = (zero, zero, zero, zero);
+    for n in [p2, p3, p4, p5, p6, p7, p8, p9] {
+        let carry0 = c0 & n;
+        c0 = c0 ^ n;
+        let carry1 = c1 & carry0;
+        c1 = c1 ^ carry0;
+        let carry2 = c2 & carry1;
+        c2 = c2 ^ carry1;
+        c3 = c3 | carry2;
+    }
+    let b_ge_2 = c1 | c2 | c3;
+    let b_le_6 = !(c3 | (c2 & c1 & c0));
+
+    let (m1, m2) = if iter == 0 {
+        (p2 & p4 & p6, p4 & p6 & p8)
+    } else {
+        (p2 & p4 & p8, p2 & p6 & p8)
+    };
+
+    p1 & a_is_1 & b_ge_2 & b_le_6 & !m1 & !m2
+}
+
+#[inline(always)]
+#[allow(clippy::too_many_arguments)]
+fn iteration<const N: usize, const AVX2: bool>(
+    im: &mut [u8],
+    win_x: usize,
+    win_y: usize,
+    win_w: usize,
+    win_h: usize,
+    w: usize,
+    h: usize,
+    iter: i32,
+) -> bool {
+    let mut diff: bool = false;
+    let min_x = if win_x == 0 { 1 } else { win_x };
+    let max_x = if win_x + win_w == w {
+        w - 1
+    } else {
+        win_x + win_w
+    };
+    let min_y = if win_y == 0 { 1 } else { win_y };
+    let max_y = if win_y + win_h == h {
+        h - 1
+    } else {
+        win_y + win_h
+    };
+    for i in min_y..max_y {
+        for j0 in (min_x..max_x).step_by(64 * N) {
+            let [p9, p2, p3] = load_row::<N, AVX2>(im, w, i - 1, j0, max_x);
+            let [p8, p1, p4] = load_row::<N, AVX2>(im, w, i, j0, max_x);
+            let [p7, p6, p5] = load_row::<N, AVX2>(im, w, i + 1, j0, max_x);
+            let marked = removable([p1, p2, p3, p4, p5, p6, p7, p8, p9], iter);
+            if !marked.any() {
+                continue;
+            }
+            diff = true;
+            for (k, &word) in marked.0.iter().enumerate() {
+                let mut word = word;
+                while word != 0 {
+                    let bit = word.trailing_zeros() as usize;
+                    im[i * w + j0 + 64 * k + bit] |= 2;
+                    word &= word - 1;
+                }
+            }
+        }
+    }
+
+    diff
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+#[allow(clippy::too_many_arguments)]
+unsafe fn iteration_avx2(
+    im: &mut [u8],
+    win_x: usize,
+    win_y: usize,
+    win_w: usize,
+    win_h: usize,
+    w: usize,
+    h: usize,
+    iter: i32,
+) -> bool {
+    iteration::<4, true>(im, win_x, win_y, win_w, win_h, w, h, iter)
+}
+
+/// Drop-in replacement for `thinning_zs_iteration`, picking the widest
+/// implementation supported by the CPU.
+#[allow(clippy::too_many_arguments)]
+pub fn thinning_zs_iteration(
+    im: &mut [u8],
+    win_x: usize,
+    win_y: usize,
+    win_w: usize,
+    win_h: usize,
+    w: usize,
+    h: usize,
+    iter: i32,
+) -> bool {
+    #[cfg(target_arch = "x86_64")]
+    if is_x86_feature_detected!("avx2") {
+        return unsafe { iteration_avx2(im, win_x, win_y, win_w, win_h, w, h, iter) };
+    }
+    thinning_zs_iteration_u64(im, win_x, win_y, win_w, win_h, w, h, iter)
+}
+
+/// Same as `thinning_zs_iteration`, but always using a single u64.
+#[allow(clippy::too_many_arguments)]
+pub fn thinning_zs_iteration_u64(
+    im: &mut [u8],
+    win_x: usize,
+    win_y: usize,
+    win_w: usize,
+    win_h: usize,
+    w: usize,
+    h: usize,
+    iter: i32,
+) -> bool {
+    iteration::<1, false>(im, win_x, win_y, win_w, win_h, w, h, iter)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_util::Rng, zhang_suen};
+
+    type Iteration = fn(&mut [u8], usize, usize, usize, usize, usize, usize, i32) -> bool;
+
+    /// Runs `iteration` and the scalar version on random images of odd widths
+    /// and on random windows of them, with some pixels already marked.
+    fn check_against_scalar(iteration: Iteration) {
+        let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+        let widths = [1, 2, 3, 63, 64, 65, 127, 129, 255, 257, 301];
+        for case in 0..2000 {
+            let w = if case < widths.len() {
+                widths[case]
+            } else {
+                1 + rng.below(320)
+            };
+            let h = 1 + rng.below(12);
+            // denser masks have fewer removable pixels
+            let density = 1 + rng.below(4);
+            let im = (0..w * h)
+                .map(|_| (rng.below(5) < density) as u8 | ((rng.below(8) == 0) as u8 * 2))
+                .collect::<Vec<_>>();
+            let (win_x, win_y) = (rng.below(w), rng.below(h));
+            let (win_w, win_h) = (1 + rng.below(w - win_x), 1 + rng.below(h - win_y));
+            for iter in 0..2 {
+                let mut expected = im.clone();
+                let expected_diff = zhang_suen::thinning_zs_iteration(
+                    &mut expected,
+                    win_x,
+                    win_y,
+                    win_w,
+                    win_h,
+                    w,
+                    h,
+                    iter,
+                );
+                let mut actual = im.clone();
+                let diff = iteration(&mut actual, win_x, win_y, win_w, win_h, w, h, iter);
+                let window = (win_x, win_y, win_w, win_h);
+                assert_eq!(actual, expected, "{w}x{h}, window {window:?}, iter {iter}");
+                assert_eq!(
+                    diff, expected_diff,
+                    "{w}x{h}, window {window:?}, iter {iter}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn u64_matches_scalar() {
+        check_against_scalar(thinning_zs_iteration_u64);
+    }
+
+    #[test]
+    fn wide_lanes_match_scalar() {
+        check_against_scalar(|im, win_x, win_y, win_w, win_h, w, h, iter| {
+            iteration::<4, false>(im, win_x, win_y, win_w, win_h, w, h, iter)
+        });
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn avx2_matches_scalar() {
+        if !is_x86_feature_detected!("avx2") {
+            eprintln!("skipping, the CPU doesn't support AVX2");
+            return;
+        }
+        check_against_scalar(|im, win_x, win_y, win_w, win_h, w, h, iter| unsafe {
+            iteration_avx2(im, win_x, win_y, win_w, win_h, w, h, iter)
+        });
+    }
+}
@@ -0,0 +1,207 @@
+use indicatif::ProgressBar;
+
+use crate::tiles;
+
+//================================
+// SKELETON PIXEL CLASSIFICATION
+//================================
+// Each skeleton pixel is classified by its crossing number, the number of
+// background to foreground transitions when walking around its 8 neighbours.
+// Pixels outside the image count as background.
+
+pub const BACKGROUND: u8 = 0;
+pub const ISOLATED: u8 = 1;
+pub const ENDPOINT: u8 = 2;
+pub const LINE: u8 = 3;
+pub const JUNCTION_3: u8 = 4;
+pub const JUNCTION_4: u8 = 5;
+
+/// RGBA colours of the classes, indexed by class.
+pub const COLORS: [[i16; 4]; 6] = [
+    [0, 0, 0, 0],
+    [255, 0, 255, 255],
+    [255, 0, 0, 255],
+    [192, 192, 192, 255],
+    [0, 0, 255, 255],
+    [0, 192, 0, 255],
+];
+
+pub fn class_name(class: u8) -> &'static str {
+    match class {
+        BACKGROUND => "background",
+        ISOLATED => "isolated",
+        ENDPOINT => "endpoint",
+        LINE => "line",
+        JUNCTION_3 => "junction",
+        JUNCTION_4 => "junction",
+        _ => unreachable!(),
+    }
+}
+
+/// An endpoint or a junction of the skeleton.
+#[derive(Clone, Copy, Debug)]
+pub struct Node {
+    pub x: usize,
+    pub y: usize,
+    pub class: u8,
+    pub crossing_number: u8,
+}
+
+pub(crate) fn classify_pixel(im: &[u8], w: usize, h: usize, i: usize, j: usize) -> (u8, u8) {
+    if im[i * w + j] & 1 == 0 {
+        return (BACKGROUND, 0);
+    }
+    let px = |di: isize, dj: isize| -> u8 {
+        let y = i as isize + di;
+        let x = j as isize + dj;
+        if y < 0 || x < 0 || y as usize >= h || x as usize >= w {
+            0
+        } else {
+            im[y as usize * w + x as usize] & 1
+        }
+    };
+    // clockwise from the top, as in the thinning
+    let p = [
+        px(-1, 0),
+        px(-1, 1),
+        px(0, 1),
+        px(1, 1),
+        px(1, 0),
+        px(1, -1),
+        px(0, -1),
+        px(-1, -1),
+    ];
+    let b: u8 = p.iter().sum();
+    let a = (0..8).filter(|&k| p[k] == 0 && p[(k + 1) % 8] == 1).count() as u8;
+    let class = if b == 0 {
+        ISOLATED
+    } else {
+        match a {
+            1 => ENDPOINT,
+            0 | 2 => LINE,
+            3 => JUNCTION_3,
+            _ => JUNCTION_4,
+        }
+    };
+    (class, a)
+}
+
+/// Classifies the pixels of the thinned image `im`, one tile at a time, optionally
+/// writing the classes to `classes`. Returns the endpoints and junctions.
+pub fn classify_tiled(
+    im: &[u8],
+    mut classes: Option<&mut [u8]>,
+    width: usize,
+    height: usize,
+    tile_width: usize,
+    tile_height: usize,
+) -> Vec<Node> {
+    let ntx = width.div_ceil(tile_width);
+    let nty = height.div_ceil(tile_height);
+    let mut counts = [0u64; 6];
+    let mut nodes = Vec::new();
+
+    log::info!("Starting classification");
+    let pb = ProgressBar::new((ntx * nty) as u64).with_message("Starting classification");
+    for (win_x, win_y, win_w, win_h) in tiles::windows(width, height, tile_width, tile_height) {
+        for i in win_y..win_y + win_h {
+            for j in win_x..win_x + win_w {
+                let (class, crossing_number) = classify_pixel(im, width, height, i, j);
+                if let Some(classes) = classes.as_deref_mut() {
+                    classes[i * width + j] = class;
+                }
+                counts[class as usize] += 1;
+                if matches!(class, ENDPOINT | JUNCTION_3 | JUNCTION_4) {
+                    nodes.push(Node {
+                        x: j,
+                        y: i,
+                        class,
+                        crossing_number,
+                    });
+                }
+            }
+        }
+        pb.inc(1);
+    }
+    pb.finish();
+
+    log::info!(
+        "Found {} isolated pixels, {} endpoints, {} line pixels, {} junctions of degree 3 and {} of degree 4 or more",
+        counts[ISOLATED as usize],
+        counts[ENDPOINT as usize],
+        counts[LINE as usize],
+        counts[JUNCTION_3 as usize],
+        counts[JUNCTION_4 as usize]
+    );
+
+    nodes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_hand_drawn_junctions() {
+        let skeleton = [
+            "..........#",
+            ".#.........",
+            ".#....###..",
+            "###....#...",
+            ".#.....#...",
+            ".#.........",
+            "...........",
+        ];
+        let expected = [
+            "..........i",
+            ".e.........",
+            ".l....e3e..",
+            "e4e....l...",
+            ".l.....e...",
+            ".e.........",
+            "...........",
+        ];
+        let (w, h) = (skeleton[0].len(), skeleton.len());
+        let im = skeleton
+            .iter()
+            .flat_map(|row| row.bytes().map(|b| (b == b'#') as u8))
+            .collect::<Vec<_>>();
+        let expected = expected
+            .iter()
+            .flat_map(|row| {
+                row.bytes().map(|b| match b {
+                    b'i' => ISOLATED,
+                    b'e' => ENDPOINT,
+                    b'l' => LINE,
+                    b'3' => JUNCTION_3,
+                    b'4' => JUNCTION_4,
+                    _ => BACKGROUND,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut classes = vec![0; w * h];
+        let mut nodes = classify_tiled(&im, Some(&mut classes), w, h, 4, 3);
+        assert_eq!(classes, expected);
+
+        nodes.sort_by_key(|n| (n.y, n.x));
+        let nodes = nodes
+            .iter()
+            .map(|n| (n.x, n.y, n.class, n.crossing_number))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            nodes,
+            [
+                (1, 1, ENDPOINT, 1),
+                (6, 2, ENDPOINT, 1),
+                (7, 2, JUNCTION_3, 3),
+                (8, 2, ENDPOINT, 1),
+                (0, 3, ENDPOINT, 1),
+                (1, 3, JUNCTION_4, 4),
+                (2, 3, ENDPOINT, 1),
+                (7, 4, ENDPOINT, 1),
+                (1, 5, ENDPOINT, 1),
+            ]
+        );
+    }
+}
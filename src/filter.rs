@@ -0,0 +1,209 @@
+use crate::{
+    label::{self, Connectivity},
+    tiles,
+};
+
+//================================
+// MASK CLEANUP
+//================================
+// Speckle removal and hole filling, done by labelling the foreground or the
+// background with `label_components_tiled` and rewriting the pixels of the
+// components that are too small. `labels` is only used as scratch space.
+
+#[allow(clippy::too_many_arguments)]
+fn rewrite_components(
+    im: &mut [u8],
+    labels: &[u32],
+    selected: &[bool],
+    value: u8,
+    width: usize,
+    height: usize,
+    tile_width: usize,
+    tile_height: usize,
+) {
+    for (win_x, win_y, win_w, win_h) in tiles::windows(width, height, tile_width, tile_height) {
+        for i in win_y..win_y + win_h {
+            for j in win_x..win_x + win_w {
+                let label = labels[i * width + j];
+                if label != 0 && selected[label as usize - 1] {
+                    im[i * width + j] = value;
+                }
+            }
+        }
+    }
+}
+
+/// Clears the foreground components with less than `min_area` pixels.
+/// Returns the number of components removed.
+#[allow(clippy::too_many_arguments)]
+pub fn remove_small_components(
+    im: &mut [u8],
+    labels: &mut [u32],
+    width: usize,
+    height: usize,
+    tile_width: usize,
+    tile_height: usize,
+    connectivity: Connectivity,
+    min_area: u64,
+) -> usize {
+    let components = label::label_components_tiled(
+        im,
+        labels,
+        1,
+        width,
+        height,
+        tile_width,
+        tile_height,
+        connectivity,
+    );
+    let small = components
+        .iter()
+        .map(|c| c.pixels < min_area)
+        .collect::<Vec<_>>();
+    rewrite_components(
+        im,
+        labels,
+        &small,
+        0,
+        width,
+        height,
+        tile_width,
+        tile_height,
+    );
+    small.iter().filter(|&&s| s).count()
+}
+
+/// Fills the holes with less than `max_area` pixels. A hole is a background component
+/// not touching the image border; `connectivity` is the one of the foreground.
+/// Returns the number of holes filled.
+#[allow(clippy::too_many_arguments)]
+pub fn fill_holes(
+    im: &mut [u8],
+    labels: &mut [u32],
+    width: usize,
+    height: usize,
+    tile_width: usize,
+    tile_height: usize,
+    connectivity: Connectivity,
+    max_area: u64,
+) -> usize {
+    let components = label::label_components_tiled(
+        im,
+        labels,
+        0,
+        width,
+        height,
+        tile_width,
+        tile_height,
+        connectivity.dual(),
+    );
+    let holes = components
+        .iter()
+        .map(|c| {
+            c.pixels < max_area
+                && c.min_x > 0
+                && c.min_y > 0
+                && c.max_x < width - 1
+                && c.max_y < height - 1
+        })
+        .collect::<Vec<_>>();
+    rewrite_components(
+        im,
+        labels,
+        &holes,
+        1,
+        width,
+        height,
+        tile_width,
+        tile_height,
+    );
+    holes.iter().filter(|&&h| h).count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(rows: &[&str]) -> Vec<u8> {
+        rows.iter()
+            .flat_map(|row| row.bytes().map(|b| (b == b'#') as u8))
+            .collect()
+    }
+
+    #[test]
+    fn removes_components_below_the_area() {
+        let mask = [
+            "##.......",
+            ".....###.",
+            ".....#...",
+            "#........",
+            ".#...####",
+        ];
+        let (w, h) = (mask[0].len(), mask.len());
+        let mut im = parse(&mask);
+        let mut labels = vec![0; w * h];
+        // 4-connected, the diagonal pair is two components of a pixel
+        let removed =
+            remove_small_components(&mut im, &mut labels, w, h, 3, 2, Connectivity::Four, 2);
+        assert_eq!(removed, 2);
+        let expected = [
+            "##.......",
+            ".....###.",
+            ".....#...",
+            ".........",
+            ".....####",
+        ];
+        assert_eq!(im, parse(&expected));
+
+        // the components with exactly `min_area` pixels are kept
+        let removed =
+            remove_small_components(&mut im, &mut labels, w, h, 3, 2, Connectivity::Eight, 4);
+        assert_eq!(removed, 1);
+        let expected = [
+            ".........",
+            ".....###.",
+            ".....#...",
+            ".........",
+            ".....####",
+        ];
+        assert_eq!(im, parse(&expected));
+    }
+
+    #[test]
+    fn fills_holes_below_the_area() {
+        let mask = [
+            "#####..####",
+            "#.#.#..#..#",
+            "#####..#..#",
+            "...#...####",
+            "...#.......",
+        ];
+        let (w, h) = (mask[0].len(), mask.len());
+        let mut labels = vec![0; w * h];
+
+        // the background touching the edges is never a hole
+        let mut im = parse(&mask);
+        let filled = fill_holes(&mut im, &mut labels, w, h, 3, 2, Connectivity::Eight, 4);
+        assert_eq!(filled, 2);
+        let expected = [
+            "#####..####",
+            "#####..#..#",
+            "#####..#..#",
+            "...#...####",
+            "...#.......",
+        ];
+        assert_eq!(im, parse(&expected));
+
+        let mut im = parse(&mask);
+        let filled = fill_holes(&mut im, &mut labels, w, h, 3, 2, Connectivity::Eight, 5);
+        assert_eq!(filled, 3);
+        let expected = [
+            "#####..####",
+            "#####..####",
+            "#####..####",
+            "...#...####",
+            "...#.......",
+        ];
+        assert_eq!(im, parse(&expected));
+    }
+}
@@ -0,0 +1,240 @@
+use std::mem;
+
+use indicatif::ProgressBar;
+
+use crate::{region::Region, tiles, zhang_suen};
+
+//================================
+// FRONTIER-BASED THINNING
+//================================
+// Zhang-Suen only ever removes pixels with a background neighbour, and whether
+// a pixel can be removed by a given sub-iteration only depends on its 3x3
+// neighbourhood. So instead of rescanning whole tiles, we keep per-tile queues
+// of candidate pixels for each of the two sub-iterations. A candidate that is
+// checked and kept is dropped from its queue, and the foreground neighbours of
+// every removed pixel are queued again for both sub-iterations. The work done
+// is proportional to the boundary of the objects instead of their area, and
+// the result is the same as `thinning_zs`.
+//
+// Like in the thinning, the second bit of a pixel marks it for removal; the
+// third and fourth bits record whether it's already queued for the first or
+// second sub-iteration. All of them are clear once the thinning is done. The
+// pixels on the outer ring of the image see past its edges through the border
+// policy of the region, and are all candidates at the start.
+
+const MARKED: u8 = 2;
+const QUEUED: [u8; 2] = [4, 8];
+
+fn removable(im: &[u8], p: usize, w: usize, iter: usize) -> bool {
+    let neighbours = [
+        im[p - w] & 1,
+        im[p - w + 1] & 1,
+        im[p + 1] & 1,
+        im[p + w + 1] & 1,
+        im[p + w] & 1,
+        im[p + w - 1] & 1,
+        im[p - 1] & 1,
+        im[p - w - 1] & 1,
+    ];
+    zhang_suen::removable(neighbours, iter as i32)
+}
+
+struct Frontier<'a> {
+    width: usize,
+    height: usize,
+    tile_width: usize,
+    tile_height: usize,
+    ntx: usize,
+    region: &'a Region,
+    // candidate pixels, by sub-iteration and tile
+    queues: [Vec<Vec<usize>>; 2],
+}
+
+impl Frontier<'_> {
+    fn tile_of(&self, p: usize) -> usize {
+        let i = p / self.width;
+        let j = p % self.width;
+        (i / self.tile_height) * self.ntx + j / self.tile_width
+    }
+
+    fn on_edge(&self, p: usize) -> bool {
+        let i = p / self.width;
+        let j = p % self.width;
+        i == 0 || j == 0 || i + 1 == self.height || j + 1 == self.width
+    }
+
+    fn removable(&self, im: &[u8], p: usize, iter: usize) -> bool {
+        if self.on_edge(p) {
+            let (i, j) = (p / self.width, p % self.width);
+            let neighbours = self
+                .region
+                .border
+                .neighbours(im, self.width, self.height, j, i);
+            zhang_suen::removable(neighbours, iter as i32)
+        } else {
+            removable(im, p, self.width, iter)
+        }
+    }
+
+    /// The neighbours of `p` inside the image.
+    fn neighbours(&self, p: usize) -> impl Iterator<Item = usize> {
+        let (width, height) = (self.width, self.height);
+        let (i, j) = (p / width, p % width);
+        (i.saturating_sub(1)..(i + 2).min(height))
+            .flat_map(move |y| {
+                (j.saturating_sub(1)..(j + 2).min(width)).map(move |x| y * width + x)
+            })
+            .filter(move |&q| q != p)
+    }
+
+    fn push(&mut self, im: &mut [u8], p: usize) {
+        let (i, j) = (p / self.width, p % self.width);
+        if im[p] & 1 == 0 || !self.region.contains(j, i) {
+            return;
+        }
+        let tile = self.tile_of(p);
+        for (&queued, queues) in QUEUED.iter().zip(&mut self.queues) {
+            if im[p] & queued == 0 {
+                im[p] |= queued;
+                queues[tile].push(p);
+            }
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.queues.iter().flatten().map(|q| q.len()).sum()
+    }
+}
+
+/// Same as `thinning_zs_tiled`, but only revisiting the pixels next to the
+/// ones removed in the previous passes.
+pub fn thinning_zs_frontier(
+    im: &mut [u8],
+    width: usize,
+    height: usize,
+    tile_width: usize,
+    tile_height: usize,
+    region: &Region,
+) {
+    let ntx = width.div_ceil(tile_width);
+    let nty = height.div_ceil(tile_height);
+    let total_tiles = ntx * nty;
+    let mut frontier = Frontier {
+        width,
+        height,
+        tile_width,
+        tile_height,
+        ntx,
+        region,
+        queues: [vec![Vec::new(); total_tiles], vec![Vec::new(); total_tiles]],
+    };
+
+    log::info!("Starting frontier detection");
+    let pb = ProgressBar::new(total_tiles as u64).with_message("Starting frontier detection");
+    for (win_x, win_y, win_w, win_h) in tiles::windows(width, height, tile_width, tile_height) {
+        let r = region.rect;
+        for i in win_y.max(r.y0)..(win_y + win_h).min(r.y1) {
+            for j in win_x.max(r.x0)..(win_x + win_w).min(r.x1) {
+                let p = i * width + j;
+                if im[p] & 1 == 0 {
+                    continue;
+                }
+                if frontier.on_edge(p) {
+                    frontier.push(im, p);
+                    continue;
+                }
+                let border = [
+                    p - width - 1,
+                    p - width,
+                    p - width + 1,
+                    p - 1,
+                    p + 1,
+                    p + width - 1,
+                    p + width,
+                    p + width + 1,
+                ]
+                .iter()
+                .any(|&q| im[q] & 1 == 0);
+                if border {
+                    frontier.push(im, p);
+                }
+            }
+        }
+        pb.inc(1);
+    }
+    pb.finish();
+
+    let mut iter = 1;
+    loop {
+        let remaining = frontier.len();
+        if remaining == 0 {
+            break;
+        }
+        log::info!("Starting iteration {iter}, {remaining} candidate pixels");
+
+        for (sub, &queued) in QUEUED.iter().enumerate() {
+            let queue = mem::replace(&mut frontier.queues[sub], vec![Vec::new(); total_tiles]);
+            let active_tiles = queue.iter().filter(|q| !q.is_empty()).count();
+
+            let pb = ProgressBar::new(active_tiles as u64)
+                .with_message(format!("Starting thinning {sub}"));
+            let mut marked = vec![Vec::new(); total_tiles];
+            for (tile, candidates) in queue.iter().enumerate() {
+                if candidates.is_empty() {
+                    continue;
+                }
+                for &p in candidates {
+                    im[p] &= !queued;
+                    if im[p] & 1 != 0 && frontier.removable(im, p, sub) {
+                        im[p] |= MARKED;
+                        marked[tile].push(p);
+                    }
+                }
+                pb.inc(1);
+            }
+            pb.finish();
+
+            let pb = ProgressBar::new(active_tiles as u64)
+                .with_message(format!("Starting pixel removal {sub}"));
+            for removed in marked.iter().filter(|m| !m.is_empty()) {
+                for &p in removed {
+                    // keep the flag of the other queue, the stale entry is skipped there
+                    im[p] &= !(1 | MARKED);
+                }
+                for &p in removed {
+                    for q in frontier.neighbours(p) {
+                        frontier.push(im, q);
+                    }
+                }
+                pb.inc(1);
+            }
+            pb.finish();
+        }
+
+        iter += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_util::{random_mask, Rng},
+        thinning_zs,
+    };
+
+    #[test]
+    fn matches_thinning_zs() {
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+        for _ in 0..300 {
+            let (w, h) = (1 + rng.below(40), 1 + rng.below(40));
+            let (tw, th) = (1 + rng.below(16), 1 + rng.below(16));
+            let im = random_mask(&mut rng, w, h);
+            let mut expected = im.clone();
+            thinning_zs(&mut expected, w, h);
+            let mut actual = im;
+            thinning_zs_frontier(&mut actual, w, h, tw, th, &Region::full(w, h));
+            assert_eq!(actual, expected, "{w}x{h}, tiles {tw}x{th}");
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use std::error::Error;
+
+use gdal::{
+    spatial_ref::{CoordTransform, SpatialRef},
+    Dataset, GeoTransform,
+};
+use gdal_sys::OSRAxisMappingStrategy;
+
+/// Which point of a pixel its map coordinates refer to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PixelAnchor {
+    Center,
+    Corner,
+}
+
+/// Converts the pixel coordinates of the outputs to map coordinates,
+/// optionally reprojecting them.
+pub struct Georef {
+    geo_transform: Option<GeoTransform>,
+    offset: f64,
+    coord_transform: Option<CoordTransform>,
+    srs: Option<SpatialRef>,
+}
+
+impl Georef {
+    /// Keeps the pixel coordinates, without a spatial reference.
+    pub fn pixels() -> Self {
+        Self {
+            geo_transform: None,
+            offset: 0.0,
+            coord_transform: None,
+            srs: None,
+        }
+    }
+
+    /// Uses the geotransform and projection of `ds`, reprojecting to `target_srs`
+    /// (anything accepted by `OSRSetFromUserInput`) if given.
+    pub fn new(
+        ds: &Dataset,
+        anchor: PixelAnchor,
+        target_srs: Option<&str>,
+    ) -> Result<Self, Box<dyn Error>> {
+        let geo_transform = ds.geo_transform()?;
+        let offset = match anchor {
+            PixelAnchor::Center => 0.5,
+            PixelAnchor::Corner => 0.0,
+        };
+        let source = ds.spatial_ref().ok();
+        if let Some(source) = &source {
+            source.set_axis_mapping_strategy(OSRAxisMappingStrategy::OAMS_TRADITIONAL_GIS_ORDER);
+        }
+
+        let (coord_transform, srs) = match target_srs {
+            Some(definition) => {
+                let source =
+                    source.ok_or("the input has no spatial reference to reproject from")?;
+                let target = SpatialRef::from_definition(definition)?;
+                target
+                    .set_axis_mapping_strategy(OSRAxisMappingStrategy::OAMS_TRADITIONAL_GIS_ORDER);
+                (Some(CoordTransform::new(&source, &target)?), Some(target))
+            }
+            None => (None, source),
+        };
+
+        Ok(Self {
+            geo_transform: Some(geo_transform),
+            offset,
+            coord_transform,
+            srs,
+        })
+    }
+
+    pub fn srs(&self) -> Option<&SpatialRef> {
+        self.srs.as_ref()
+    }
+
+    /// Transforms the points in-place.
+    pub fn transform(&self, xs: &mut [f64], ys: &mut [f64]) -> Result<(), Box<dyn Error>> {
+        if let Some(gt) = &self.geo_transform {
+            for (x, y) in xs.iter_mut().zip(ys.iter_mut()) {
+                let px = *x + self.offset;
+                let py = *y + self.offset;
+                *x = gt[0] + px * gt[1] + py * gt[2];
+                *y = gt[3] + px * gt[4] + py * gt[5];
+            }
+        }
+        if let Some(ct) = &self.coord_transform {
+            ct.transform_coords(xs, ys, &mut [])?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,161 @@
+use indicatif::ProgressBar;
+
+use crate::{
+    label::{self, Connectivity},
+    raster::Pixel,
+    tiles::{self, Rect},
+};
+
+//================================
+// INCREMENTAL RE-THINNING
+//================================
+// Zhang-Suen only looks at the 3x3 neighbourhood of a pixel, so the skeleton of
+// an 8-connected component doesn't depend on the rest of the mask. After local
+// edits, the components of the new mask that don't touch an edited pixel are
+// exactly components of the old mask, and their skeleton can be copied from the
+// previous run. Only the other ones need to be thinned again. A thinned
+// skeleton has no removable pixels, so the copied parts are left alone by the
+// thinning, and it's enough to start it on the tiles of the edited components.
+
+/// Replaces the components of the mask `im` not touching a pixel for which
+/// `changed` is true by their skeleton in `previous`, labelling them in the
+/// `labels` scratch buffer. Returns, for each tile, the rectangle enclosing
+/// the components to thin again.
+#[allow(clippy::too_many_arguments)]
+pub fn splice_previous<T: Pixel>(
+    im: &mut [u8],
+    previous: &[T],
+    labels: &mut [u32],
+    width: usize,
+    height: usize,
+    tile_width: usize,
+    tile_height: usize,
+    changed: impl Fn(usize, usize) -> bool,
+) -> Vec<Rect> {
+    let components = label::label_components_tiled(
+        im,
+        labels,
+        1,
+        width,
+        height,
+        tile_width,
+        tile_height,
+        Connectivity::Eight,
+    );
+    let windows = tiles::windows(width, height, tile_width, tile_height)
+        .map(|(win_x, win_y, win_w, win_h)| Rect::new(win_x, win_y, win_w, win_h))
+        .collect::<Vec<_>>();
+
+    // a removed pixel can split a component, so look around the edited pixels too
+    let mut affected = vec![false; components.len() + 1];
+    log::info!("Finding the edited components");
+    let pb = ProgressBar::new(windows.len() as u64).with_message("Finding the edited components");
+    for window in &windows {
+        for i in window.y0..window.y1 {
+            for j in window.x0..window.x1 {
+                if !changed(j, i) {
+                    continue;
+                }
+                for y in i.saturating_sub(1)..(i + 2).min(height) {
+                    for x in j.saturating_sub(1)..(j + 2).min(width) {
+                        affected[labels[y * width + x] as usize] = true;
+                    }
+                }
+            }
+        }
+        pb.inc(1);
+    }
+    pb.finish();
+    affected[0] = false;
+
+    let count = affected.iter().filter(|&&a| a).count();
+    log::info!("Thinning {count} of {} components again", components.len());
+
+    log::info!("Copying the previous skeleton");
+    let pb = ProgressBar::new(windows.len() as u64).with_message("Copying the previous skeleton");
+    for window in &windows {
+        for i in window.y0..window.y1 {
+            for j in window.x0..window.x1 {
+                let p = i * width + j;
+                let id = labels[p] as usize;
+                if id != 0 && !affected[id] {
+                    im[p] = previous[p].is_set() as u8;
+                }
+            }
+        }
+        pb.inc(1);
+    }
+    pb.finish();
+
+    let ntx = width.div_ceil(tile_width);
+    let mut dirty = vec![Rect::EMPTY; windows.len()];
+    for (component, _) in components
+        .iter()
+        .zip(&affected[1..])
+        .filter(|(_, &affected)| affected)
+    {
+        let bbox = Rect {
+            x0: component.min_x,
+            y0: component.min_y,
+            x1: component.max_x + 1,
+            y1: component.max_y + 1,
+        };
+        for ti_y in bbox.y0 / tile_height..=(bbox.y1 - 1) / tile_height {
+            for ti_x in bbox.x0 / tile_width..=(bbox.x1 - 1) / tile_width {
+                let tile = ti_y * ntx + ti_x;
+                dirty[tile] = dirty[tile].union(&bbox.intersect(&windows[tile]));
+            }
+        }
+    }
+    dirty
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{region::Region, thinning_zs, thinning_zs_dirty};
+
+    const W: usize = 40;
+    const H: usize = 20;
+
+    fn fill(im: &mut [u8], x0: usize, y0: usize, x1: usize, y1: usize) {
+        for i in y0..y1 {
+            im[i * W + x0..i * W + x1].fill(1);
+        }
+    }
+
+    #[test]
+    fn rethins_only_the_edited_component() {
+        let mut old = vec![0; W * H];
+        fill(&mut old, 2, 2, 14, 10);
+        fill(&mut old, 22, 4, 36, 16);
+        let mut previous = old.clone();
+        thinning_zs(&mut previous, W, H);
+
+        // extend the left rectangle downwards
+        let mut im = old.clone();
+        fill(&mut im, 2, 10, 8, 18);
+        let changed = |x: usize, y: usize| im[y * W + x] != old[y * W + x];
+        let mut expected = im.clone();
+        thinning_zs(&mut expected, W, H);
+
+        let mut spliced = im.clone();
+        let mut labels = vec![0; W * H];
+        let dirty = splice_previous(&mut spliced, &previous, &mut labels, W, H, 8, 8, changed);
+        // the right rectangle is copied from the previous skeleton
+        for i in 0..H {
+            for j in 20..W {
+                assert_eq!(spliced[i * W + j], previous[i * W + j], "({j}, {i})");
+            }
+        }
+        // and the tiles only covering it aren't thinned again
+        for (tile, rect) in dirty.iter().enumerate() {
+            if tile % 5 >= 2 {
+                assert_eq!(*rect, Rect::EMPTY, "tile {tile}");
+            }
+        }
+
+        thinning_zs_dirty(&mut spliced, W, H, 8, 8, &Region::full(W, H), dirty);
+        assert_eq!(spliced, expected);
+    }
+}
@@ -0,0 +1,470 @@
+use std::{
+    error::Error,
+    fs::File,
+    io::{BufWriter, Write},
+};
+
+use indicatif::ProgressBar;
+
+use crate::tiles;
+
+//================================
+// CONNECTED COMPONENT LABELLING
+//================================
+// Every tile is labelled on its own with provisional labels, the labels
+// touching across tile seams are joined with a union-find, and a last pass
+// rewrites the raster with consecutive component ids starting at 1.
+// Background pixels get the label 0.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Connectivity {
+    Four,
+    Eight,
+}
+
+impl Connectivity {
+    /// The connectivity to use for the background, so that the foreground and
+    /// background components don't cross each other.
+    pub fn dual(self) -> Self {
+        match self {
+            Connectivity::Four => Connectivity::Eight,
+            Connectivity::Eight => Connectivity::Four,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Component {
+    pub pixels: u64,
+    pub min_x: usize,
+    pub min_y: usize,
+    pub max_x: usize,
+    pub max_y: usize,
+    pub skeleton_length: f64,
+}
+
+struct UnionFind {
+    parent: Vec<u32>,
+}
+
+impl UnionFind {
+    fn new() -> Self {
+        // label 0 is the background
+        Self { parent: vec![0] }
+    }
+
+    fn len(&self) -> usize {
+        self.parent.len()
+    }
+
+    fn make_set(&mut self) -> u32 {
+        let label = self.parent.len() as u32;
+        assert!(label < u32::MAX, "too many provisional labels");
+        self.parent.push(label);
+        label
+    }
+
+    fn find(&mut self, mut x: u32) -> u32 {
+        while self.parent[x as usize] != x {
+            let grandparent = self.parent[self.parent[x as usize] as usize];
+            self.parent[x as usize] = grandparent;
+            x = grandparent;
+        }
+        x
+    }
+
+    // the root is always the smallest label, so that the final ids
+    // follow the order in which the components were first seen
+    fn union(&mut self, a: u32, b: u32) {
+        let a = self.find(a);
+        let b = self.find(b);
+        if a < b {
+            self.parent[b as usize] = a;
+        } else if b < a {
+            self.parent[a as usize] = b;
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn label_tile(
+    im: &[u8],
+    labels: &mut [u32],
+    uf: &mut UnionFind,
+    win_x: usize,
+    win_y: usize,
+    win_w: usize,
+    win_h: usize,
+    w: usize,
+    value: u8,
+    connectivity: Connectivity,
+) {
+    for i in win_y..win_y + win_h {
+        for j in win_x..win_x + win_w {
+            if im[i * w + j] & 1 != value {
+                labels[i * w + j] = 0;
+                continue;
+            }
+
+            // previously visited neighbours inside the tile
+            let mut neighbours = [0u32; 4];
+            if j > win_x {
+                neighbours[0] = labels[i * w + j - 1];
+            }
+            if i > win_y {
+                neighbours[1] = labels[(i - 1) * w + j];
+                if connectivity == Connectivity::Eight {
+                    if j > win_x {
+                        neighbours[2] = labels[(i - 1) * w + j - 1];
+                    }
+                    if j + 1 < win_x + win_w {
+                        neighbours[3] = labels[(i - 1) * w + j + 1];
+                    }
+                }
+            }
+
+            let mut label = 0;
+            for &n in neighbours.iter().filter(|&&n| n != 0) {
+                if label == 0 {
+                    label = n;
+                } else {
+                    uf.union(label, n);
+                }
+            }
+            if label == 0 {
+                label = uf.make_set();
+            }
+            labels[i * w + j] = label;
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn join_seams(
+    im: &[u8],
+    labels: &[u32],
+    uf: &mut UnionFind,
+    win_x: usize,
+    win_y: usize,
+    win_w: usize,
+    win_h: usize,
+    w: usize,
+    h: usize,
+    value: u8,
+    connectivity: Connectivity,
+) {
+    // left seam, against the last column of the tile on the left
+    if win_x > 0 {
+        let j = win_x;
+        for i in win_y..win_y + win_h {
+            if im[i * w + j] & 1 != value {
+                continue;
+            }
+            let label = labels[i * w + j];
+            if im[i * w + j - 1] & 1 == value {
+                uf.union(label, labels[i * w + j - 1]);
+            }
+            if connectivity == Connectivity::Eight {
+                if i > 0 && im[(i - 1) * w + j - 1] & 1 == value {
+                    uf.union(label, labels[(i - 1) * w + j - 1]);
+                }
+                if i + 1 < h && im[(i + 1) * w + j - 1] & 1 == value {
+                    uf.union(label, labels[(i + 1) * w + j - 1]);
+                }
+            }
+        }
+    }
+    // top seam, against the last row of the tile above
+    if win_y > 0 {
+        let i = win_y;
+        for j in win_x..win_x + win_w {
+            if im[i * w + j] & 1 != value {
+                continue;
+            }
+            let label = labels[i * w + j];
+            if im[(i - 1) * w + j] & 1 == value {
+                uf.union(label, labels[(i - 1) * w + j]);
+            }
+            if connectivity == Connectivity::Eight {
+                if j > 0 && im[(i - 1) * w + j - 1] & 1 == value {
+                    uf.union(label, labels[(i - 1) * w + j - 1]);
+                }
+                if j + 1 < w && im[(i - 1) * w + j + 1] & 1 == value {
+                    uf.union(label, labels[(i - 1) * w + j + 1]);
+                }
+            }
+        }
+    }
+}
+
+/// Labels the connected components of the pixels of `im` with `& 1 == value` into `labels`,
+/// processing one tile at a time. Use `value = 1` for the foreground and `0` for the background.
+/// Returns the components, the one with id `k` being at index `k - 1`.
+/// `skeleton_length` is left at zero, see [`measure_skeletons`].
+#[allow(clippy::too_many_arguments)]
+pub fn label_components_tiled(
+    im: &[u8],
+    labels: &mut [u32],
+    value: u8,
+    width: usize,
+    height: usize,
+    tile_width: usize,
+    tile_height: usize,
+    connectivity: Connectivity,
+) -> Vec<Component> {
+    let ntx = width.div_ceil(tile_width);
+    let nty = height.div_ceil(tile_height);
+    let total_tiles = (ntx * nty) as u64;
+    let mut uf = UnionFind::new();
+
+    log::info!("Starting tile labelling");
+    let pb = ProgressBar::new(total_tiles).with_message("Starting tile labelling");
+    for (win_x, win_y, win_w, win_h) in tiles::windows(width, height, tile_width, tile_height) {
+        label_tile(
+            im,
+            labels,
+            &mut uf,
+            win_x,
+            win_y,
+            win_w,
+            win_h,
+            width,
+            value,
+            connectivity,
+        );
+        pb.inc(1);
+    }
+    pb.finish();
+
+    log::info!("Starting seam merging");
+    let pb = ProgressBar::new(total_tiles).with_message("Starting seam merging");
+    for (win_x, win_y, win_w, win_h) in tiles::windows(width, height, tile_width, tile_height) {
+        join_seams(
+            im,
+            labels,
+            &mut uf,
+            win_x,
+            win_y,
+            win_w,
+            win_h,
+            width,
+            height,
+            value,
+            connectivity,
+        );
+        pb.inc(1);
+    }
+    pb.finish();
+
+    // roots come before the other members of their set, so one pass is enough
+    let mut ids = vec![0u32; uf.len()];
+    let mut components = Vec::new();
+    for label in 1..uf.len() as u32 {
+        let root = uf.find(label);
+        if root == label {
+            components.push(Component {
+                pixels: 0,
+                min_x: usize::MAX,
+                min_y: usize::MAX,
+                max_x: 0,
+                max_y: 0,
+                skeleton_length: 0.0,
+            });
+            ids[label as usize] = components.len() as u32;
+        } else {
+            ids[label as usize] = ids[root as usize];
+        }
+    }
+    log::info!("Found {} components", components.len());
+
+    log::info!("Starting relabelling");
+    let pb = ProgressBar::new(total_tiles).with_message("Starting relabelling");
+    for (win_x, win_y, win_w, win_h) in tiles::windows(width, height, tile_width, tile_height) {
+        for i in win_y..win_y + win_h {
+            for j in win_x..win_x + win_w {
+                let label = labels[i * width + j];
+                if label == 0 {
+                    continue;
+                }
+                let id = ids[label as usize];
+                labels[i * width + j] = id;
+                let c = &mut components[id as usize - 1];
+                c.pixels += 1;
+                c.min_x = c.min_x.min(j);
+                c.min_y = c.min_y.min(i);
+                c.max_x = c.max_x.max(j);
+                c.max_y = c.max_y.max(i);
+            }
+        }
+        pb.inc(1);
+    }
+    pb.finish();
+
+    components
+}
+
+/// Adds the length of the skeleton pixels in `im` to the component they belong to.
+/// Pixels are joined to their 8-neighbours, with diagonal steps only counted when
+/// there is no path through an orthogonal neighbour.
+pub fn measure_skeletons(
+    im: &[u8],
+    labels: &[u32],
+    width: usize,
+    height: usize,
+    tile_width: usize,
+    tile_height: usize,
+    components: &mut [Component],
+) {
+    let fg = |i: usize, j: usize| im[i * width + j] & 1 != 0;
+    for (win_x, win_y, win_w, win_h) in tiles::windows(width, height, tile_width, tile_height) {
+        for i in win_y..win_y + win_h {
+            for j in win_x..win_x + win_w {
+                let label = labels[i * width + j];
+                if label == 0 || !fg(i, j) {
+                    continue;
+                }
+                let same = |i: usize, j: usize| fg(i, j) && labels[i * width + j] == label;
+                let east = j + 1 < width && same(i, j + 1);
+                let west = j > 0 && same(i, j - 1);
+                let south = i + 1 < height && same(i + 1, j);
+                let mut length = east as u8 as f64 + south as u8 as f64;
+                if i + 1 < height {
+                    if j + 1 < width && same(i + 1, j + 1) && !east && !south {
+                        length += std::f64::consts::SQRT_2;
+                    }
+                    if j > 0 && same(i + 1, j - 1) && !west && !south {
+                        length += std::f64::consts::SQRT_2;
+                    }
+                }
+                components[label as usize - 1].skeleton_length += length;
+            }
+        }
+    }
+}
+
+/// Returns the id of the component `polyline` belongs to, or 0 if none of its
+/// vertices falls on a labelled pixel.
+pub fn polyline_component(labels: &[u32], width: usize, polyline: &[[usize; 2]]) -> u32 {
+    polyline
+        .iter()
+        .map(|p| labels[p[1] * width + p[0]])
+        .find(|&label| label != 0)
+        .unwrap_or(0)
+}
+
+pub fn write_components(path: &str, components: &[Component]) -> Result<(), Box<dyn Error>> {
+    let mut out = BufWriter::new(File::create(path)?);
+    writeln!(out, "id,pixels,min_x,min_y,max_x,max_y,skeleton_length")?;
+    for (i, c) in components.iter().enumerate() {
+        writeln!(
+            out,
+            "{},{},{},{},{},{},{:.3}",
+            i + 1,
+            c.pixels,
+            c.min_x,
+            c.min_y,
+            c.max_x,
+            c.max_y,
+            c.skeleton_length
+        )?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{random_mask, Rng};
+
+    /// Labels the foreground of the whole image by flood fill.
+    fn flood_fill(im: &[u8], w: usize, h: usize, connectivity: Connectivity) -> Vec<u32> {
+        let mut labels = vec![0; w * h];
+        let mut next = 0;
+        for start in 0..w * h {
+            if im[start] == 0 || labels[start] != 0 {
+                continue;
+            }
+            next += 1;
+            labels[start] = next;
+            let mut stack = vec![start];
+            while let Some(p) = stack.pop() {
+                let (i, j) = ((p / w) as isize, (p % w) as isize);
+                for (dy, dx) in [
+                    (-1, -1),
+                    (-1, 0),
+                    (-1, 1),
+                    (0, -1),
+                    (0, 1),
+                    (1, -1),
+                    (1, 0),
+                    (1, 1),
+                ] {
+                    let (y, x) = (i + dy, j + dx);
+                    if connectivity == Connectivity::Four && dy != 0 && dx != 0
+                        || y < 0
+                        || x < 0
+                        || y >= h as isize
+                        || x >= w as isize
+                    {
+                        continue;
+                    }
+                    let q = y as usize * w + x as usize;
+                    if im[q] != 0 && labels[q] == 0 {
+                        labels[q] = next;
+                        stack.push(q);
+                    }
+                }
+            }
+        }
+        labels
+    }
+
+    #[test]
+    fn tiled_labels_match_whole_image() {
+        let mut rng = Rng(0xbf58_476d_1ce4_e5b9);
+        for _ in 0..300 {
+            let (w, h) = (1 + rng.below(40), 1 + rng.below(40));
+            let (tw, th) = (1 + rng.below(16), 1 + rng.below(16));
+            let im = random_mask(&mut rng, w, h);
+            for connectivity in [Connectivity::Four, Connectivity::Eight] {
+                let mut labels = vec![0; w * h];
+                let components =
+                    label_components_tiled(&im, &mut labels, 1, w, h, tw, th, connectivity);
+                let expected = flood_fill(&im, w, h, connectivity);
+                let count = expected.iter().copied().max().unwrap_or(0) as usize;
+                let context = format!("{w}x{h}, tiles {tw}x{th}, {connectivity:?}");
+                assert_eq!(components.len(), count, "{context}");
+
+                // the components across the seams got a single label each
+                let mut to_expected = vec![0; count + 1];
+                for (&label, &e) in labels.iter().zip(&expected) {
+                    assert_eq!(label == 0, e == 0, "{context}");
+                    let mapped = &mut to_expected[label as usize];
+                    if *mapped == 0 {
+                        *mapped = e;
+                    }
+                    assert_eq!(*mapped, e, "{context}");
+                }
+                for (k, component) in components.iter().enumerate() {
+                    let e = to_expected[k + 1];
+                    let pixels = (0..w * h).filter(|&p| expected[p] == e);
+                    assert_eq!(component.pixels, pixels.clone().count() as u64, "{context}");
+                    assert_eq!(
+                        component.min_x,
+                        pixels.clone().map(|p| p % w).min().unwrap()
+                    );
+                    assert_eq!(
+                        component.max_x,
+                        pixels.clone().map(|p| p % w).max().unwrap()
+                    );
+                    assert_eq!(
+                        component.min_y,
+                        pixels.clone().map(|p| p / w).min().unwrap()
+                    );
+                    assert_eq!(component.max_y, pixels.map(|p| p / w).max().unwrap());
+                }
+            }
+        }
+    }
+}
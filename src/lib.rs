@@ -0,0 +1,44 @@
+//! Zhang-Suen thinning and skeleton tracing of rasters too large to fit in memory.
+//!
+//! The modules reading and writing files need the `gdal` feature, enabled by default.
+
+mod bitslice;
+pub mod classify;
+pub mod filter;
+pub mod frontier;
+#[cfg(feature = "gdal")]
+pub mod geo;
+#[cfg(feature = "gdal")]
+pub mod incremental;
+pub mod label;
+pub mod morphology;
+#[cfg(feature = "gdal")]
+pub mod multiclass;
+pub mod prune;
+#[cfg(feature = "gdal")]
+pub mod raster;
+pub mod rasterize;
+pub mod refine;
+pub mod region;
+pub mod rings;
+pub mod skeleton;
+#[cfg(test)]
+mod test_util;
+pub mod tiles;
+#[cfg(feature = "gdal")]
+pub mod vector;
+pub mod walk;
+mod zhang_suen;
+
+/// The single Zhang-Suen sub-iterations, only public for the fuzz targets.
+#[doc(hidden)]
+pub mod iteration {
+    pub use crate::bitslice::{
+        thinning_zs_iteration as bitsliced, thinning_zs_iteration_u64 as bitsliced_u64,
+    };
+    pub use crate::zhang_suen::thinning_zs_iteration as scalar;
+}
+
+pub use zhang_suen::{
+    thinning_zs, thinning_zs_dirty, thinning_zs_tiled, thinning_zs_tiled_progress,
+};
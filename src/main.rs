@@ -2,301 +2,201 @@ use std::{
     error::Error,
     fs::{File, OpenOptions},
     io::{BufWriter, Write},
-    ptr, slice,
 };
 
 use gdal::{Dataset, DatasetOptions, GdalOpenFlags};
-use gdal_sys::GDALRWFlag::GF_Write;
-use indicatif::ProgressBar;
+use gdal_sys::{
+    GDALDataType,
+    GDALRWFlag::{GF_Read, GF_Write},
+};
 use log::LevelFilter;
 use memmap2::{Mmap, MmapMut};
+use thinning::{
+    classify, filter, frontier,
+    geo::Georef,
+    incremental, label, morphology, multiclass, prune,
+    raster::{self, ScratchMem, VirtualMem},
+    rasterize, refine,
+    region::{Border, Region},
+    rings,
+    skeleton::{self, Tracer},
+    thinning_zs_dirty, thinning_zs_tiled, vector, walk,
+};
 
-mod skeleton;
-
-//================================
-// RASTER SKELETONIZATION
-//================================
-// Binary image thinning (skeletonization) in-place.
-// Implements Zhang-Suen algorithm.
-// http://agcggs680.pbworks.com/f/Zhan-Suen_algorithm.pdf
-fn thinning_zs_iteration(
-    im: &mut [u8],
-    win_x: usize,
-    win_y: usize,
-    win_w: usize,
-    win_h: usize,
-    w: usize,
-    h: usize,
-    iter: i32,
-) -> bool {
-    let mut diff: bool = false;
-    let min_x = if win_x == 0 { 1 } else { win_x };
-    let max_x = if win_x + win_w == w {
-        w - 1
-    } else {
-        win_x + win_w
-    };
-    let min_y = if win_y == 0 { 1 } else { win_y };
-    let max_y = if win_y + win_h == h {
-        h - 1
-    } else {
-        win_y + win_h
-    };
-    for i in min_y..max_y {
-        for j in min_x..max_x {
-            let p1: u8 = im[i * w + j] & 1;
-            if p1 == 0 {
-                continue;
-            }
-
-            let p2: u8 = im[(i - 1) * w + j] & 1;
-            let p3: u8 = im[(i - 1) * w + j + 1] & 1;
-            let p4: u8 = im[(i) * w + j + 1] & 1;
-            let p5: u8 = im[(i + 1) * w + j + 1] & 1;
-            let p6: u8 = im[(i + 1) * w + j] & 1;
-            let p7: u8 = im[(i + 1) * w + j - 1] & 1;
-            let p8: u8 = im[(i) * w + j - 1] & 1;
-            let p9: u8 = im[(i - 1) * w + j - 1] & 1;
-            let a: u8 = (p2 == 0 && p3 == 1) as u8
-                + (p3 == 0 && p4 == 1) as u8
-                + (p4 == 0 && p5 == 1) as u8
-                + (p5 == 0 && p6 == 1) as u8
-                + (p6 == 0 && p7 == 1) as u8
-                + (p7 == 0 && p8 == 1) as u8
-                + (p8 == 0 && p9 == 1) as u8
-                + (p9 == 0 && p2 == 1) as u8;
-            let b: u8 = p2 + p3 + p4 + p5 + p6 + p7 + p8 + p9;
-            let m1: u8 = if iter == 0 {
-                p2 * p4 * p6
-            } else {
-                p2 * p4 * p8
-            };
-            let m2: u8 = if iter == 0 {
-                p4 * p6 * p8
-            } else {
-                p2 * p6 * p8
-            };
-            if a == 1 && (b >= 2 && b <= 6) && m1 == 0 && m2 == 0 {
-                diff = true;
-                im[i * w + j] |= 2;
-            }
-        }
-    }
-
-    return diff;
-}
-
-fn thinning_zs_post(
-    im: &mut [u8],
-    win_x: usize,
-    win_y: usize,
-    win_w: usize,
-    win_h: usize,
-    w: usize,
-) {
-    for i in win_y..win_y + win_h {
-        for j in win_x..win_x + win_w {
-            let marker = im[i * w + j] >> 1;
-            let old = im[i * w + j] & 1;
-            let new = old & (!marker);
-            if new != old {
-                im[i * w + j] = new;
-            }
-        }
-    }
-}
-
-pub fn thinning_zs(im: &mut [u8], w: usize, h: usize) {
-    let mut iter = 0;
-    loop {
-        dbg!(iter);
-        let mut diff = false;
-        if dbg!(thinning_zs_iteration(im, 0, 0, w, h, w, h, 0)) {
-            diff = true;
-            thinning_zs_post(im, 0, 0, w, h, w);
-        }
-        if dbg!(thinning_zs_iteration(im, 0, 0, w, h, w, h, 1)) {
-            diff = true;
-            thinning_zs_post(im, 0, 0, w, h, w);
-        }
-        if !diff {
-            break;
-        }
-        iter += 1;
-    }
-}
-
-pub fn thinning_zs_tiled(
-    im: &mut [u8],
-    width: usize,
-    height: usize,
-    tile_width: usize,
-    tile_height: usize,
-) {
-    let ntx = (width + tile_width - 1) / tile_width;
-    let nty = (height + tile_height - 1) / tile_height;
-    let total_tiles = ntx * nty;
-
-    const FLAG_NONE: u8 = 0;
-    const FLAG_DONE: u8 = 1;
-    const FLAG_CHANGED_H: u8 = 2;
-    const FLAG_CHANGED_V: u8 = 4;
-    let mut tile_flags = vec![FLAG_NONE; total_tiles];
-
-    let mut iter = 1;
-    loop {
-        let remaining_tiles = tile_flags.iter().filter(|&f| f & FLAG_DONE == 0).count();
-        if remaining_tiles == 0 {
-            break;
-        }
-        let pb = ProgressBar::new(remaining_tiles as u64).with_message("Starting thinning H");
-        log::info!("Starting iteration {iter}, {remaining_tiles}/{total_tiles}");
-        log::info!("Starting thinning H");
-
-        for ti_y in 0..nty {
-            for ti_x in 0..ntx {
-                if tile_flags[ti_y * ntx + ti_x] & FLAG_DONE != 0
-                    && (ti_x == 0 || tile_flags[ti_y * ntx + ti_x - 1] & FLAG_DONE != 0)
-                    && (ti_y == 0 || tile_flags[(ti_y - 1) * ntx + ti_x] & FLAG_DONE != 0)
-                    && (ti_x == ntx - 1 || tile_flags[ti_y * ntx + ti_x + 1] & FLAG_DONE != 0)
-                    && (ti_y == nty - 1 || tile_flags[(ti_y + 1) * ntx + ti_x] & FLAG_DONE != 0)
-                {
-                    continue;
-                }
-                let win_x = ti_x * tile_width;
-                let win_y = ti_y * tile_height;
-                let win_w = tile_width.min(width - win_x);
-                let win_h = tile_height.min(height - win_y);
-                if thinning_zs_iteration(im, win_x, win_y, win_w, win_h, width, height, 0) {
-                    tile_flags[ti_y * ntx + ti_x] |= FLAG_CHANGED_H;
-                } else {
-                    tile_flags[ti_y * ntx + ti_x] &= !FLAG_CHANGED_H;
-                }
-                pb.inc(1);
-            }
-        }
-        pb.finish();
-
-        let remaining_tiles = tile_flags
-            .iter()
-            .filter(|&f| f & FLAG_CHANGED_H != 0)
-            .count();
-        let pb = ProgressBar::new(remaining_tiles as u64).with_message("Starting pixel removal H");
-        log::info!("Starting pixel removal H");
-        for ti_y in 0..nty {
-            for ti_x in 0..ntx {
-                if tile_flags[ti_y * ntx + ti_x] & FLAG_CHANGED_H == 0 {
-                    continue;
-                }
-                let win_x = ti_x * tile_width;
-                let win_y = ti_y * tile_height;
-                let win_w = tile_width.min(width - win_x);
-                let win_h = tile_height.min(height - win_y);
-                thinning_zs_post(im, win_x, win_y, win_w, win_h, width);
-                pb.inc(1);
-            }
-        }
-        pb.finish();
+use crate::args::Args;
 
-        let remaining_tiles = tile_flags.iter().filter(|&f| f & FLAG_DONE == 0).count();
-        let pb = ProgressBar::new(remaining_tiles as u64).with_message("Starting thinning V");
-        // thinning_zs_post(im, 0, 0, w, h, w);
-        log::info!("Starting thinning V");
-        for ti_y in 0..nty {
-            for ti_x in 0..ntx {
-                if tile_flags[ti_y * ntx + ti_x] & FLAG_DONE != 0
-                    && (ti_x == 0 || tile_flags[ti_y * ntx + ti_x - 1] & FLAG_DONE != 0)
-                    && (ti_y == 0 || tile_flags[(ti_y - 1) * ntx + ti_x] & FLAG_DONE != 0)
-                    && (ti_x == ntx - 1 || tile_flags[ti_y * ntx + ti_x + 1] & FLAG_DONE != 0)
-                    && (ti_y == nty - 1 || tile_flags[(ti_y + 1) * ntx + ti_x] & FLAG_DONE != 0)
-                {
-                    continue;
-                }
-                let win_x = ti_x * tile_width;
-                let win_y = ti_y * tile_height;
-                let win_w = tile_width.min(width - win_x);
-                let win_h = tile_height.min(height - win_y);
-                if thinning_zs_iteration(im, win_x, win_y, win_w, win_h, width, height, 1) {
-                    tile_flags[ti_y * ntx + ti_x] |= FLAG_CHANGED_V;
-                } else {
-                    tile_flags[ti_y * ntx + ti_x] &= !FLAG_CHANGED_V;
-                }
-                pb.inc(1);
-            }
-        }
-        pb.finish();
+mod args;
 
-        let remaining_tiles = tile_flags
-            .iter()
-            .filter(|&f| f & FLAG_CHANGED_V != 0)
-            .count();
-        let pb = ProgressBar::new(remaining_tiles as u64).with_message("Starting pixel removal V");
-        log::info!("Starting pixel removal V");
-        // thinning_zs_post(im, 0, 0, w, h, w);
-        for ti_y in 0..nty {
-            for ti_x in 0..ntx {
-                if tile_flags[ti_y * ntx + ti_x] & FLAG_CHANGED_H == 0
-                    && tile_flags[ti_y * ntx + ti_x] & FLAG_CHANGED_V == 0
-                {
-                    tile_flags[ti_y * ntx + ti_x] |= FLAG_DONE;
-                    continue;
-                }
-                if tile_flags[ti_y * ntx + ti_x] & FLAG_CHANGED_V == 0 {
-                    continue;
-                }
-                let win_x = ti_x * tile_width;
-                let win_y = ti_y * tile_height;
-                let win_w = tile_width.min(width - win_x);
-                let win_h = tile_height.min(height - win_y);
-                thinning_zs_post(im, win_x, win_y, win_w, win_h, width);
-                pb.inc(1);
-            }
-        }
-        pb.finish();
+fn main() -> Result<(), Box<dyn Error>> {
+    let args = Args::parse()?;
 
-        iter += 1;
-    }
-}
+    let mut builder = env_logger::Builder::new();
+    builder.filter_level(log::LevelFilter::Info);
+    builder.parse_env("RUST_LOG");
+    builder.init();
 
-fn main() -> Result<(), Box<dyn Error>> {
-    let file = std::env::args().nth(1).unwrap();
     let mut ds = Dataset::open_ex(
-        &file,
+        &args.input,
         DatasetOptions {
-            open_flags: GdalOpenFlags::GDAL_OF_UPDATE,
+            // the input is left alone when splitting the classes
+            open_flags: if args.split_classes.is_some() {
+                GdalOpenFlags::GDAL_OF_READONLY
+            } else {
+                GdalOpenFlags::GDAL_OF_UPDATE
+            },
             ..DatasetOptions::default()
         },
     )?;
-    let band = ds.rasterband(1)?;
-    let mut pixel_space = 0;
-    let mut line_space = 0i64;
-    let mem = unsafe {
-        gdal_sys::GDALGetVirtualMemAuto(
-            band.c_rasterband(),
-            GF_Write,
-            &mut pixel_space as *mut _,
-            &mut line_space as *mut _,
-            ptr::null::<i8>() as _,
-        )
+    let band = ds.rasterband(args.band)?;
+    let flag = if args.split_classes.is_some() {
+        GF_Read
+    } else {
+        GF_Write
     };
+    let data_type = band.band_type();
+    let mut mem = VirtualMem::new(&band, flag)?;
     let (width, height) = band.size();
-    assert_eq!(pixel_space, 1);
-    assert_eq!(line_space, width as i64);
     let (tile_width, tile_height) = band.block_size();
-    dbg!((width, height));
-    dbg!((tile_width, tile_height));
-    let data = unsafe { gdal_sys::CPLVirtualMemGetAddr(mem) } as *mut u8;
-    let len = unsafe { gdal_sys::CPLVirtualMemGetSize(mem) };
-    let im = unsafe { slice::from_raw_parts_mut(data, len) };
+    log::debug!("Image of {width}x{height} pixels, blocks of {tile_width}x{tile_height}");
+
+    if let (Some(selection), Some(path)) = (&args.split_classes, &args.class_skeletons) {
+        let border = Border::new(&ds, &args.border, args.band, width, height)?;
+        let region = match &args.region {
+            Some(spec) => Region::new(&ds, spec, width, height)?,
+            None => Region::full(width, height),
+        }
+        .with_border(border);
+        multiclass::thin_classes(
+            &ds,
+            mem.pixels(data_type)?,
+            band.no_data_value(),
+            selection,
+            path,
+            args.class_bands,
+            &format!("{}.scratch", args.input),
+            width,
+            height,
+            tile_width,
+            tile_height,
+            |mask| {
+                if args.frontier {
+                    frontier::thinning_zs_frontier(
+                        mask,
+                        width,
+                        height,
+                        tile_width,
+                        tile_height,
+                        &region,
+                    );
+                } else {
+                    thinning_zs_tiled(mask, width, height, tile_width, tile_height, &region);
+                }
+                if let Some(max_length) = args.prune {
+                    prune::prune_spurs(
+                        mask,
+                        width,
+                        height,
+                        tile_width,
+                        tile_height,
+                        &region,
+                        max_length,
+                    );
+                }
+            },
+        )?;
+        return Ok(());
+    }
+
+    // other pixel types are copied to a Byte mask, the skeleton pixels keeping their value
+    let mut mask_mem = None;
+    if data_type != GDALDataType::GDT_Byte {
+        let mut scratch_mem = ScratchMem::new(format!("{}.mask", args.input), width * height)?;
+        thinning::with_pixels!(mem.pixels(data_type)?, src => raster::to_mask(
+            src,
+            scratch_mem.as_mut_slice::<u8>(),
+            width,
+            height,
+            tile_width,
+            tile_height,
+        ));
+        mask_mem = Some(scratch_mem);
+    }
+    let im = match mask_mem.as_mut() {
+        Some(scratch_mem) => scratch_mem.as_mut_slice::<u8>(),
+        None => mem.as_mut_slice::<u8>(),
+    };
 
     // let file = OpenOptions::new().read(true).write(true).open(&file)?;
     // let mut im = unsafe { MmapMut::map_mut(&file)? };
     // let im = im.as_mut();
 
-    let mut builder = env_logger::Builder::new();
-    builder.filter_level(log::LevelFilter::Info);
-    builder.parse_env("RUST_LOG");
-    builder.init();
+    for &step in &args.morphology {
+        morphology::morphology_tiled(im, width, height, tile_width, tile_height, step);
+    }
+
+    if args.min_area.is_some() || args.fill_holes.is_some() {
+        let mut scratch_mem = ScratchMem::new(
+            format!("{}.scratch", args.input),
+            width * height * std::mem::size_of::<u32>(),
+        )?;
+        let scratch = scratch_mem.as_mut_slice::<u32>();
+        if let Some(min_area) = args.min_area {
+            let removed = filter::remove_small_components(
+                im,
+                scratch,
+                width,
+                height,
+                tile_width,
+                tile_height,
+                args.connectivity,
+                min_area,
+            );
+            log::info!("Removed {removed} components smaller than {min_area} pixels");
+        }
+        if let Some(max_area) = args.fill_holes {
+            let filled = filter::fill_holes(
+                im,
+                scratch,
+                width,
+                height,
+                tile_width,
+                tile_height,
+                args.connectivity,
+                max_area,
+            );
+            log::info!("Filled {filled} holes smaller than {max_area} pixels");
+        }
+    }
+
+    let labels_ds = match &args.labels {
+        Some(path) => Some(raster::create_like::<u32>(
+            &ds,
+            path,
+            (tile_width, tile_height),
+            1,
+        )?),
+        None => None,
+    };
+    let labels_band = labels_ds.as_ref().map(|ds| ds.rasterband(1)).transpose()?;
+    let mut labels_mem = labels_band
+        .as_ref()
+        .map(|band| VirtualMem::new(band, GF_Write))
+        .transpose()?;
+    let mut labels = labels_mem.as_mut().map(|mem| mem.as_mut_slice::<u32>());
+
+    let mut components = match labels.as_deref_mut() {
+        Some(labels) => label::label_components_tiled(
+            im,
+            labels,
+            1,
+            width,
+            height,
+            tile_width,
+            tile_height,
+            args.connectivity,
+        ),
+        None => Vec::new(),
+    };
 
     // for i in 0..height * width {
     //     if im[i as usize] > 128 {
@@ -310,19 +210,214 @@ fn main() -> Result<(), Box<dyn Error>> {
     // let height = 599280;
 
     // thinning_zs(im, width, height);
-    thinning_zs_tiled(im, width, height, tile_width, tile_height);
+    let border = Border::new(&ds, &args.border, args.band, width, height)?;
+    let mut region = match &args.region {
+        Some(spec) => Region::new(&ds, spec, width, height)?,
+        None => Region::full(width, height),
+    }
+    .with_border(border);
+    let mut dirty = None;
+    if let Some(path) = &args.previous {
+        let previous_ds = Dataset::open(path)?;
+        let previous_band = previous_ds.rasterband(1)?;
+        if previous_band.size() != (width, height) {
+            return Err(format!("{path} doesn't have the same size as the input").into());
+        }
+        let mut previous_mem = VirtualMem::new(&previous_band, GF_Read)?;
+        let changed_ds = args.changed.as_ref().map(Dataset::open).transpose()?;
+        let changed_band = changed_ds.as_ref().map(|ds| ds.rasterband(1)).transpose()?;
+        if let Some(band) = &changed_band {
+            if band.size() != (width, height) {
+                return Err("the changed raster doesn't have the same size as the input".into());
+            }
+            if band.band_type() != GDALDataType::GDT_Byte {
+                return Err("the changed raster must be of type Byte".into());
+            }
+        }
+        let mut changed_mem = changed_band
+            .as_ref()
+            .map(|band| VirtualMem::new(band, GF_Read))
+            .transpose()?;
+        let changed = changed_mem.as_mut().map(|mem| &*mem.as_mut_slice::<u8>());
+        let mut scratch_mem = ScratchMem::new(
+            format!("{}.scratch", args.input),
+            width * height * std::mem::size_of::<u32>(),
+        )?;
+        let labels = scratch_mem.as_mut_slice::<u32>();
+        let changed = |x: usize, y: usize| match changed {
+            Some(changed) => changed[y * width + x] != 0,
+            None => region.contains(x, y),
+        };
+        let previous = previous_mem.pixels(previous_band.band_type())?;
+        dirty = Some(
+            thinning::with_pixels!(previous, previous => incremental::splice_previous(
+                im,
+                previous,
+                labels,
+                width,
+                height,
+                tile_width,
+                tile_height,
+                changed,
+            )),
+        );
+        // the edited components can reach outside of the region
+        region = Region::full(width, height).with_border(region.border);
+    }
 
-    // let skeleton = skeleton::trace_skeleton(im, width, height, 0, 0, 100000, 100000, 10, 999);
-    // let mut out = BufWriter::new(File::create("skeleton.csv")?);
-    // for i in 0..skeleton.len() {
-    //     for j in 0..skeleton[i].len() {
-    //         write!(out, "{},{} ", skeleton[i][j][0], skeleton[i][j][1])?;
-    //     }
-    //     writeln!(out)?;
-    // }
+    if args.frontier {
+        frontier::thinning_zs_frontier(im, width, height, tile_width, tile_height, &region);
+    } else if let Some(dirty) = dirty {
+        thinning_zs_dirty(im, width, height, tile_width, tile_height, &region, dirty);
+    } else {
+        thinning_zs_tiled(im, width, height, tile_width, tile_height, &region);
+    }
+
+    if let Some(max_length) = args.prune {
+        prune::prune_spurs(
+            im,
+            width,
+            height,
+            tile_width,
+            tile_height,
+            &region,
+            max_length,
+        );
+    }
+
+    if let Some(labels) = labels.as_deref() {
+        label::measure_skeletons(
+            im,
+            labels,
+            width,
+            height,
+            tile_width,
+            tile_height,
+            &mut components,
+        );
+    }
+    if let Some(path) = &args.components {
+        label::write_components(path, &components)?;
+    }
+
+    let georef = if args.georef {
+        Georef::new(&ds, args.pixel_anchor, args.target_srs.as_deref())?
+    } else {
+        Georef::pixels()
+    };
+
+    if args.classes.is_some() || args.nodes.is_some() {
+        let classes_ds = match &args.classes {
+            Some(path) => Some(raster::create_like::<u8>(
+                &ds,
+                path,
+                (tile_width, tile_height),
+                1,
+            )?),
+            None => None,
+        };
+        let mut classes_band = classes_ds.as_ref().map(|ds| ds.rasterband(1)).transpose()?;
+        if let Some(band) = classes_band.as_mut() {
+            raster::set_palette(band, &classify::COLORS);
+        }
+        let mut classes_mem = classes_band
+            .as_ref()
+            .map(|band| VirtualMem::new(band, GF_Write))
+            .transpose()?;
+        let nodes = classify::classify_tiled(
+            im,
+            classes_mem.as_mut().map(|mem| mem.as_mut_slice::<u8>()),
+            width,
+            height,
+            tile_width,
+            tile_height,
+        );
+        if let Some(path) = &args.nodes {
+            vector::write_nodes(path, &nodes, &georef)?;
+        }
+    }
+
+    if let Some(path) = &args.trace {
+        let r = region.rect;
+        let skeleton = match args.tracer {
+            Tracer::Heuristic => skeleton::trace_skeleton_par(
+                im,
+                width,
+                height,
+                r.x0,
+                r.y0,
+                r.width(),
+                r.height(),
+                10,
+                999,
+            ),
+            Tracer::Exact => {
+                walk::trace_pixels(im, width, height, r.x0, r.y0, r.width(), r.height())
+            }
+        };
+        let skeleton = region.clip_polylines(skeleton);
+        let (skeleton, rings) = rings::close_rings(skeleton);
+        let ids = labels.as_deref().map(|labels| {
+            skeleton
+                .iter()
+                .map(|polyline| label::polyline_component(labels, width, polyline))
+                .collect::<Vec<_>>()
+        });
+        let polylines = match args.refine {
+            Some(radius) => refine::refine_polylines(im, width, height, &skeleton, radius),
+            None => skeleton
+                .iter()
+                .map(|polyline| {
+                    polyline
+                        .iter()
+                        .map(|p| [p[0] as f64, p[1] as f64])
+                        .collect()
+                })
+                .collect(),
+        };
+        vector::write_polylines(
+            path,
+            &polylines,
+            &rings,
+            args.ring_polygons,
+            ids.as_deref(),
+            &georef,
+        )?;
 
-    unsafe { gdal_sys::CPLVirtualMemFree(mem) };
+        if let Some(path) = &args.rasterize {
+            let burnt_ds = raster::create_like::<u8>(&ds, path, (tile_width, tile_height), 1)?;
+            let burnt_band = burnt_ds.rasterband(1)?;
+            let mut burnt_mem = VirtualMem::new(&burnt_band, GF_Write)?;
+            let burnt = burnt_mem.as_mut_slice::<u8>();
+            let widths = vec![args.burn_width.unwrap_or(1.0); polylines.len()];
+            rasterize::burn_polylines(burnt, width, height, &polylines, &widths);
+            let comparison =
+                rasterize::compare(im, burnt, width, height, tile_width, tile_height, &region);
+            if let Some(path) = &args.compare {
+                rasterize::write_comparison(path, &comparison)?;
+            }
+        }
+    }
 
+    if let Some(scratch_mem) = mask_mem.as_mut() {
+        let mask = scratch_mem.as_mut_slice::<u8>();
+        thinning::with_pixels!(mem.pixels(data_type)?, dst => raster::apply_mask(
+            dst,
+            mask,
+            width,
+            height,
+            tile_width,
+            tile_height,
+        ));
+    }
+
+    drop(labels_mem);
+    drop(labels_band);
+    drop(mem);
+
+    if let Some(mut labels_ds) = labels_ds {
+        labels_ds.flush_cache();
+    }
     ds.flush_cache();
 
     Ok(())
@@ -0,0 +1,268 @@
+use std::str::FromStr;
+
+use indicatif::ProgressBar;
+
+use crate::tiles;
+
+//================================
+// BINARY MORPHOLOGY
+//================================
+// Each pass reads the mask bit (`& 1`) of a tile plus a halo of `radius` pixels
+// around it and stores the result in the second bit. Once all the tiles are
+// done, the result is moved over the mask bit, like in the thinning.
+// Pixels outside the image are ignored, so objects touching the border are
+// neither grown nor eroded from outside.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Shape {
+    Square,
+    Cross,
+    Disk,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Operation {
+    Dilate,
+    Erode,
+    Open,
+    Close,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Step {
+    pub operation: Operation,
+    pub shape: Shape,
+    pub radius: usize,
+}
+
+impl FromStr for Step {
+    type Err = String;
+
+    /// Parses `operation:shape:radius`, e.g. `open:disk:2`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts = s.split(':').collect::<Vec<_>>();
+        if parts.len() != 3 {
+            return Err(format!("expected operation:shape:radius, got {s}"));
+        }
+        let operation = match parts[0] {
+            "dilate" => Operation::Dilate,
+            "erode" => Operation::Erode,
+            "open" => Operation::Open,
+            "close" => Operation::Close,
+            op => return Err(format!("unknown operation {op}")),
+        };
+        let shape = match parts[1] {
+            "square" => Shape::Square,
+            "cross" => Shape::Cross,
+            "disk" => Shape::Disk,
+            shape => return Err(format!("unknown structuring element {shape}")),
+        };
+        let radius = parts[2]
+            .parse()
+            .map_err(|_| format!("invalid radius {}", parts[2]))?;
+        Ok(Self {
+            operation,
+            shape,
+            radius,
+        })
+    }
+}
+
+/// Returns the `(dy, dx)` offsets covered by the structuring element.
+fn structuring_element(shape: Shape, radius: usize) -> Vec<(isize, isize)> {
+    let r = radius as isize;
+    let mut offsets = Vec::new();
+    for dy in -r..=r {
+        for dx in -r..=r {
+            let inside = match shape {
+                Shape::Square => true,
+                Shape::Cross => dx == 0 || dy == 0,
+                Shape::Disk => dx * dx + dy * dy <= r * r,
+            };
+            if inside {
+                offsets.push((dy, dx));
+            }
+        }
+    }
+    offsets
+}
+
+#[allow(clippy::too_many_arguments)]
+fn morphology_tile(
+    im: &mut [u8],
+    offsets: &[(isize, isize)],
+    dilate: bool,
+    win_x: usize,
+    win_y: usize,
+    win_w: usize,
+    win_h: usize,
+    w: usize,
+    h: usize,
+) {
+    // a dilation sets the pixels that see any foreground pixel,
+    // an erosion clears the ones that see any background pixel
+    let needle = dilate as u8;
+    for i in win_y..win_y + win_h {
+        for j in win_x..win_x + win_w {
+            let old = im[i * w + j] & 1;
+            let hit = old == needle
+                || offsets.iter().any(|&(dy, dx)| {
+                    let y = i as isize + dy;
+                    let x = j as isize + dx;
+                    y >= 0
+                        && x >= 0
+                        && (y as usize) < h
+                        && (x as usize) < w
+                        && im[y as usize * w + x as usize] & 1 == needle
+                });
+            let new = if hit { needle } else { old };
+            im[i * w + j] = old | (new << 1);
+        }
+    }
+}
+
+fn morphology_post(
+    im: &mut [u8],
+    win_x: usize,
+    win_y: usize,
+    win_w: usize,
+    win_h: usize,
+    w: usize,
+) {
+    for i in win_y..win_y + win_h {
+        for j in win_x..win_x + win_w {
+            im[i * w + j] >>= 1;
+        }
+    }
+}
+
+fn morphology_pass(
+    im: &mut [u8],
+    width: usize,
+    height: usize,
+    tile_width: usize,
+    tile_height: usize,
+    offsets: &[(isize, isize)],
+    dilate: bool,
+) {
+    let ntx = width.div_ceil(tile_width);
+    let nty = height.div_ceil(tile_height);
+    let total_tiles = (ntx * nty) as u64;
+    let name = if dilate { "dilation" } else { "erosion" };
+
+    log::info!("Starting {name}");
+    let pb = ProgressBar::new(total_tiles).with_message(format!("Starting {name}"));
+    for (win_x, win_y, win_w, win_h) in tiles::windows(width, height, tile_width, tile_height) {
+        morphology_tile(
+            im, offsets, dilate, win_x, win_y, win_w, win_h, width, height,
+        );
+        pb.inc(1);
+    }
+    pb.finish();
+
+    let pb = ProgressBar::new(total_tiles).with_message(format!("Finishing {name}"));
+    for (win_x, win_y, win_w, win_h) in tiles::windows(width, height, tile_width, tile_height) {
+        morphology_post(im, win_x, win_y, win_w, win_h, width);
+        pb.inc(1);
+    }
+    pb.finish();
+}
+
+/// Applies a morphological operation to the mask in-place, one tile at a time.
+/// `im` must only contain 0 and 1.
+pub fn morphology_tiled(
+    im: &mut [u8],
+    width: usize,
+    height: usize,
+    tile_width: usize,
+    tile_height: usize,
+    step: Step,
+) {
+    let offsets = structuring_element(step.shape, step.radius);
+    let passes: &[bool] = match step.operation {
+        Operation::Dilate => &[true],
+        Operation::Erode => &[false],
+        Operation::Open => &[false, true],
+        Operation::Close => &[true, false],
+    };
+    for &dilate in passes {
+        morphology_pass(im, width, height, tile_width, tile_height, &offsets, dilate);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{random_mask, Rng};
+
+    const SHAPES: [Shape; 3] = [Shape::Square, Shape::Cross, Shape::Disk];
+
+    fn step(operation: Operation, shape: Shape, radius: usize) -> Step {
+        Step {
+            operation,
+            shape,
+            radius,
+        }
+    }
+
+    #[test]
+    fn dilates_a_pixel_into_the_element_and_erodes_it_back() {
+        let (w, h) = (11, 11);
+        for shape in SHAPES {
+            for radius in 1..=3 {
+                let mut im = vec![0; w * h];
+                im[5 * w + 5] = 1;
+                morphology_tiled(&mut im, w, h, 4, 3, step(Operation::Dilate, shape, radius));
+                let mut expected = vec![0; w * h];
+                for (dy, dx) in structuring_element(shape, radius) {
+                    expected[(5 + dy) as usize * w + (5 + dx) as usize] = 1;
+                }
+                assert_eq!(im, expected, "{shape:?} {radius}");
+
+                morphology_tiled(&mut im, w, h, 4, 3, step(Operation::Erode, shape, radius));
+                let mut expected = vec![0; w * h];
+                expected[5 * w + 5] = 1;
+                assert_eq!(im, expected, "{shape:?} {radius}");
+            }
+        }
+    }
+
+    #[test]
+    fn opening_and_closing_are_idempotent() {
+        let mut rng = Rng(0x94d0_49bb_1331_11eb);
+        for _ in 0..50 {
+            let radius = 1 + rng.below(3);
+            // keep the objects away from the edges, which are ignored
+            let margin = 2 * radius + 1;
+            let (iw, ih) = (1 + rng.below(30), 1 + rng.below(30));
+            let (w, h) = (iw + 2 * margin, ih + 2 * margin);
+            let inner = random_mask(&mut rng, iw, ih);
+            let mut im = vec![0; w * h];
+            for i in 0..ih {
+                im[(i + margin) * w + margin..][..iw].copy_from_slice(&inner[i * iw..][..iw]);
+            }
+            let (tw, th) = (1 + rng.below(16), 1 + rng.below(16));
+            for shape in SHAPES {
+                for (operation, shrinks) in [(Operation::Open, true), (Operation::Close, false)] {
+                    let step = step(operation, shape, radius);
+                    let context = format!("{w}x{h}, tiles {tw}x{th}, {step:?}");
+                    let mut once = im.clone();
+                    morphology_tiled(&mut once, w, h, tw, th, step);
+                    let mut whole = im.clone();
+                    morphology_tiled(&mut whole, w, h, w, h, step);
+                    assert_eq!(once, whole, "{context}");
+                    // an opening only removes pixels, a closing only adds some
+                    assert!(
+                        once.iter()
+                            .zip(&im)
+                            .all(|(&a, &b)| (a <= b) == shrinks || a == b),
+                        "{context}"
+                    );
+                    let mut twice = once.clone();
+                    morphology_tiled(&mut twice, w, h, tw, th, step);
+                    assert_eq!(twice, once, "{context}");
+                }
+            }
+        }
+    }
+}
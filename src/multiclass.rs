@@ -0,0 +1,235 @@
+use std::{collections::BTreeMap, error::Error, str::FromStr};
+
+use gdal::Dataset;
+use gdal_sys::GDALRWFlag::GF_Write;
+use indicatif::ProgressBar;
+
+use crate::{
+    raster::{self, Pixel, Pixels, ScratchMem, VirtualMem},
+    tiles,
+};
+
+//================================
+// MULTI-CLASS SKELETONS
+//================================
+// Instead of a binary mask, the band can hold integer class labels, e.g. a
+// land-cover product. Each selected class is copied into a scratch mask and
+// thinned on its own, so the input is left untouched. The skeletons go either
+// into a single raster holding the class of each skeleton pixel (the classes
+// don't overlap, so neither do their skeletons), or into one band per class.
+
+/// Which class values to skeletonise.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ClassSelection {
+    /// Every value in the band except 0 and the nodata value.
+    All,
+    Values(Vec<i64>),
+}
+
+impl FromStr for ClassSelection {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "all" {
+            return Ok(ClassSelection::All);
+        }
+        s.split(',')
+            .map(|v| v.trim().parse().ok())
+            .collect::<Option<Vec<i64>>>()
+            .map(ClassSelection::Values)
+            .ok_or_else(|| format!("invalid class list {s}, expected all or v1,v2,..."))
+    }
+}
+
+fn histogram<T: Pixel>(
+    im: &[T],
+    width: usize,
+    height: usize,
+    tile_width: usize,
+    tile_height: usize,
+) -> BTreeMap<i64, u64> {
+    let mut counts = BTreeMap::new();
+    for (win_x, win_y, win_w, win_h) in tiles::windows(width, height, tile_width, tile_height) {
+        for i in win_y..win_y + win_h {
+            for &v in &im[i * width + win_x..i * width + win_x + win_w] {
+                *counts.entry(v.value()).or_insert(0) += 1;
+            }
+        }
+    }
+    counts
+}
+
+/// Thins each selected class of the label band `pixels` of `ds` separately with
+/// `thin`, writing the skeletons to `path`: a single band of the same type with
+/// the class of each skeleton pixel, or with `separate_bands`, one Byte band per
+/// class, in increasing order of the class values.
+pub fn thin_classes(
+    ds: &Dataset,
+    pixels: Pixels,
+    nodata: Option<f64>,
+    selection: &ClassSelection,
+    path: &str,
+    separate_bands: bool,
+    scratch_path: &str,
+    width: usize,
+    height: usize,
+    tile_width: usize,
+    tile_height: usize,
+    mut thin: impl FnMut(&mut [u8]),
+) -> Result<(), Box<dyn Error>> {
+    crate::with_pixels!(pixels, im => thin_classes_typed(
+        ds,
+        im,
+        nodata,
+        selection,
+        path,
+        separate_bands,
+        scratch_path,
+        width,
+        height,
+        tile_width,
+        tile_height,
+        &mut thin,
+    ))
+}
+
+fn thin_classes_typed<T: Pixel>(
+    ds: &Dataset,
+    im: &[T],
+    nodata: Option<f64>,
+    selection: &ClassSelection,
+    path: &str,
+    separate_bands: bool,
+    scratch_path: &str,
+    width: usize,
+    height: usize,
+    tile_width: usize,
+    tile_height: usize,
+    thin: &mut dyn FnMut(&mut [u8]),
+) -> Result<(), Box<dyn Error>> {
+    log::info!("Counting the classes");
+    let counts = histogram(im, width, height, tile_width, tile_height);
+    let classes = match selection {
+        ClassSelection::All => counts
+            .keys()
+            .copied()
+            .filter(|&v| v != 0 && nodata != Some(v as f64))
+            .collect::<Vec<_>>(),
+        ClassSelection::Values(values) => {
+            let mut values = values.clone();
+            values.sort_unstable();
+            values.dedup();
+            for v in &values {
+                if !counts.contains_key(v) {
+                    log::warn!("Class {v} is not present in the input");
+                }
+            }
+            values
+        }
+    };
+    if classes.is_empty() {
+        return Err("no classes to skeletonise".into());
+    }
+
+    let block_size = (tile_width, tile_height);
+    let out_ds = if separate_bands {
+        raster::create_like::<u8>(ds, path, block_size, classes.len())?
+    } else {
+        raster::create_like::<T>(ds, path, block_size, 1)?
+    };
+    let mut scratch_mem = ScratchMem::new(scratch_path, width * height)?;
+    let mask = scratch_mem.as_mut_slice::<u8>();
+
+    for (k, &class) in classes.iter().enumerate() {
+        log::info!(
+            "Skeletonising class {class} ({}/{}), {} pixels",
+            k + 1,
+            classes.len(),
+            counts.get(&class).copied().unwrap_or(0)
+        );
+        for (win_x, win_y, win_w, win_h) in tiles::windows(width, height, tile_width, tile_height) {
+            for i in win_y..win_y + win_h {
+                for j in win_x..win_x + win_w {
+                    mask[i * width + j] = (im[i * width + j].value() == class) as u8;
+                }
+            }
+        }
+
+        thin(mask);
+
+        let band_index = if separate_bands { k + 1 } else { 1 };
+        let out_band = out_ds.rasterband(band_index as isize)?;
+        let mut out_mem = VirtualMem::new(&out_band, GF_Write)?;
+        let pb =
+            ProgressBar::new(tiles::windows(width, height, tile_width, tile_height).count() as u64)
+                .with_message(format!("Writing class {class}"));
+        if separate_bands {
+            let out = out_mem.as_mut_slice::<u8>();
+            for (win_x, win_y, win_w, win_h) in
+                tiles::windows(width, height, tile_width, tile_height)
+            {
+                for i in win_y..win_y + win_h {
+                    for j in win_x..win_x + win_w {
+                        out[i * width + j] = mask[i * width + j] & 1;
+                    }
+                }
+                pb.inc(1);
+            }
+        } else {
+            // the skeleton pixels of the class still hold it in the input
+            let out = out_mem.as_mut_slice::<T>();
+            for (win_x, win_y, win_w, win_h) in
+                tiles::windows(width, height, tile_width, tile_height)
+            {
+                for i in win_y..win_y + win_h {
+                    for j in win_x..win_x + win_w {
+                        if mask[i * width + j] & 1 != 0 {
+                            out[i * width + j] = im[i * width + j];
+                        }
+                    }
+                }
+                pb.inc(1);
+            }
+        }
+        pb.finish();
+    }
+
+    if separate_bands {
+        for (k, class) in classes.iter().enumerate() {
+            log::info!("Band {} holds class {class}", k + 1);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_class_selections() {
+        assert_eq!("all".parse(), Ok(ClassSelection::All));
+        assert_eq!(
+            "3, -1,20".parse(),
+            Ok(ClassSelection::Values(vec![3, -1, 20]))
+        );
+        assert!("".parse::<ClassSelection>().is_err());
+        assert!("1,,2".parse::<ClassSelection>().is_err());
+        assert!("1,a".parse::<ClassSelection>().is_err());
+    }
+
+    #[test]
+    fn counts_the_classes_across_tiles() {
+        let (w, h) = (7, 5);
+        let im = (0..w * h)
+            .map(|p| ((p * 7919) % 5) as i16 - 1)
+            .collect::<Vec<_>>();
+        let mut expected = BTreeMap::new();
+        for &v in &im {
+            *expected.entry(i64::from(v)).or_insert(0) += 1;
+        }
+        for (tile_width, tile_height) in [(1, 1), (3, 2), (7, 5), (8, 8)] {
+            assert_eq!(histogram(&im, w, h, tile_width, tile_height), expected);
+        }
+    }
+}
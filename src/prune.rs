@@ -0,0 +1,173 @@
+use indicatif::ProgressBar;
+
+use crate::{
+    classify::{self, ENDPOINT},
+    region::Region,
+    tiles,
+};
+
+//================================
+// SPUR PRUNING
+//================================
+// Noise on the mask boundary leaves short branches on the skeleton. Starting
+// from each endpoint, we walk along the skeleton until the path reaches a
+// pixel where it branches; if that happens within `max_length` pixels, the
+// branch is removed, but not the branching pixel. Lines with two endpoints and
+// no junction are left alone, however short. A single pass is made, so the
+// branches left behind by a removed spur are kept.
+
+// clockwise from the top, the 4-neighbours at the even indices
+const RING: [(isize, isize); 8] = [
+    (-1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+    (1, 0),
+    (1, -1),
+    (0, -1),
+    (-1, -1),
+];
+
+enum Step {
+    Next(usize),
+    Branch,
+    End,
+}
+
+/// Where the path goes after its last pixel, ignoring the pixels already on it.
+fn step(im: &[u8], w: usize, h: usize, path: &[usize]) -> Step {
+    let p = *path.last().unwrap();
+    let (i, j) = ((p / w) as isize, (p % w) as isize);
+    let mut ring = [None; 8];
+    for (k, &(di, dj)) in RING.iter().enumerate() {
+        let (y, x) = (i + di, j + dj);
+        if y < 0 || x < 0 || y as usize >= h || x as usize >= w {
+            continue;
+        }
+        let q = y as usize * w + x as usize;
+        if im[q] & 1 != 0 && !path.contains(&q) {
+            ring[k] = Some(q);
+        }
+    }
+    let runs = (0..8)
+        .filter(|&k| ring[k].is_none() && ring[(k + 1) % 8].is_some())
+        .count();
+    match runs {
+        0 => Step::End,
+        1 => {
+            // prefer the 4-neighbours, so that staircases are followed pixel by pixel
+            let next = (0..8)
+                .step_by(2)
+                .chain((1..8).step_by(2))
+                .find_map(|k| ring[k])
+                .unwrap();
+            Step::Next(next)
+        }
+        _ => Step::Branch,
+    }
+}
+
+/// Removes the branches of the thinned image `im` going from an endpoint to
+/// a junction in at most `max_length` pixels, if they're inside `region`.
+/// Returns the number of removed branches.
+pub fn prune_spurs(
+    im: &mut [u8],
+    width: usize,
+    height: usize,
+    tile_width: usize,
+    tile_height: usize,
+    region: &Region,
+    max_length: usize,
+) -> usize {
+    let ntx = width.div_ceil(tile_width);
+    let nty = height.div_ceil(tile_height);
+
+    log::info!("Starting pruning");
+    let pb = ProgressBar::new((ntx * nty) as u64).with_message("Starting pruning");
+    let mut removed = 0;
+    let mut path = Vec::new();
+    for (win_x, win_y, win_w, win_h) in tiles::windows(width, height, tile_width, tile_height) {
+        for i in win_y..win_y + win_h {
+            for j in win_x..win_x + win_w {
+                if !region.contains(j, i)
+                    || classify::classify_pixel(im, width, height, i, j).0 != ENDPOINT
+                {
+                    continue;
+                }
+                path.clear();
+                path.push(i * width + j);
+                let spur = loop {
+                    match step(im, width, height, &path) {
+                        Step::Next(q) => {
+                            if path.len() > max_length || !region.contains(q % width, q / width) {
+                                break false;
+                            }
+                            path.push(q);
+                        }
+                        Step::Branch => break true,
+                        Step::End => break false,
+                    }
+                };
+                if spur {
+                    // the last pixel is where the path branches
+                    path.pop();
+                    for &p in &path {
+                        im[p] &= !1;
+                    }
+                    removed += 1;
+                }
+            }
+        }
+        pb.inc(1);
+    }
+    pb.finish();
+
+    log::info!("Removed {removed} spurs of at most {max_length} pixels");
+    removed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const W: usize = 21;
+    const H: usize = 12;
+
+    /// A horizontal line on row 3, with a vertical branch of `spur` pixels
+    /// going down from its middle.
+    fn tee(spur: usize) -> Vec<u8> {
+        let mut im = vec![0; W * H];
+        im[3 * W + 1..3 * W + 20].fill(1);
+        for i in 4..4 + spur {
+            im[i * W + 10] = 1;
+        }
+        im
+    }
+
+    fn prune(im: &mut [u8], max_length: usize) -> usize {
+        prune_spurs(im, W, H, 8, 8, &Region::full(W, H), max_length)
+    }
+
+    #[test]
+    fn removes_short_spur() {
+        let mut im = tee(3);
+        assert_eq!(prune(&mut im, 5), 1);
+        assert_eq!(im, tee(0));
+    }
+
+    #[test]
+    fn keeps_long_spur() {
+        let mut im = tee(6);
+        assert_eq!(prune(&mut im, 3), 0);
+        assert_eq!(im, tee(6));
+    }
+
+    #[test]
+    fn keeps_short_line_without_junction() {
+        let mut im = vec![0; W * H];
+        im[5 * W + 2..5 * W + 6].fill(1);
+        let expected = im.clone();
+        assert_eq!(prune(&mut im, 10), 0);
+        assert_eq!(im, expected);
+    }
+}
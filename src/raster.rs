@@ -0,0 +1,298 @@
+use std::{
+    error::Error,
+    fs::{self, OpenOptions},
+    io,
+    marker::PhantomData,
+    mem,
+    path::PathBuf,
+    ptr, slice,
+};
+
+use gdal::{
+    raster::{
+        ColorEntry, ColorTable, GdalType, PaletteInterpretation, RasterBand, RasterCreationOption,
+    },
+    Dataset, Driver,
+};
+use gdal_sys::{CPLVirtualMem, GDALDataType, GDALRWFlag};
+use memmap2::MmapMut;
+
+use crate::tiles;
+
+/// A raster band mapped into memory with `GDALGetVirtualMemAuto`.
+pub struct VirtualMem<'a> {
+    mem: *mut CPLVirtualMem,
+    pixel_space: i32,
+    line_space: i64,
+    width: usize,
+    _band: PhantomData<&'a ()>,
+}
+
+impl<'a> VirtualMem<'a> {
+    pub fn new(band: &'a RasterBand, flag: GDALRWFlag::Type) -> Result<Self, Box<dyn Error>> {
+        let mut pixel_space = 0;
+        let mut line_space = 0i64;
+        let mem = unsafe {
+            gdal_sys::GDALGetVirtualMemAuto(
+                band.c_rasterband(),
+                flag,
+                &mut pixel_space as *mut _,
+                &mut line_space as *mut _,
+                ptr::null::<i8>() as _,
+            )
+        };
+        if mem.is_null() {
+            return Err("GDALGetVirtualMemAuto failed".into());
+        }
+        Ok(Self {
+            mem,
+            pixel_space,
+            line_space,
+            width: band.size().0,
+            _band: PhantomData,
+        })
+    }
+
+    /// Returns the band as a contiguous array of `T`, which must match the band type.
+    pub fn as_mut_slice<T>(&mut self) -> &mut [T] {
+        assert_eq!(self.pixel_space as usize, mem::size_of::<T>());
+        assert_eq!(self.line_space, (self.width * mem::size_of::<T>()) as i64);
+        let data = unsafe { gdal_sys::CPLVirtualMemGetAddr(self.mem) } as *mut T;
+        let len = unsafe { gdal_sys::CPLVirtualMemGetSize(self.mem) } / mem::size_of::<T>();
+        assert_eq!(data as usize % mem::align_of::<T>(), 0);
+        unsafe { slice::from_raw_parts_mut(data, len) }
+    }
+}
+
+/// Integer pixel types supported for the input.
+pub trait Pixel: GdalType + Copy + Default + PartialEq {
+    /// Whether the pixel is in the mask. For Byte images only the lowest bit
+    /// counts, like in the thinning, for the others any non-zero value.
+    fn is_set(self) -> bool;
+
+    fn value(self) -> i64;
+}
+
+impl Pixel for u8 {
+    fn is_set(self) -> bool {
+        self & 1 != 0
+    }
+
+    fn value(self) -> i64 {
+        self as i64
+    }
+}
+
+macro_rules! impl_pixel {
+    ($($t:ty),*) => {
+        $(
+            impl Pixel for $t {
+                fn is_set(self) -> bool {
+                    self != 0
+                }
+
+                fn value(self) -> i64 {
+                    self as i64
+                }
+            }
+        )*
+    };
+}
+
+impl_pixel!(u16, i16, u32, i32);
+
+/// The pixels of a band, for each of the supported types.
+pub enum Pixels<'a> {
+    U8(&'a mut [u8]),
+    U16(&'a mut [u16]),
+    I16(&'a mut [i16]),
+    U32(&'a mut [u32]),
+    I32(&'a mut [i32]),
+}
+
+/// Evaluates `$body` with `$slice` bound to the slice of `$pixels`, whatever its type.
+#[macro_export]
+macro_rules! with_pixels {
+    ($pixels:expr, $slice:ident => $body:expr) => {
+        match $pixels {
+            $crate::raster::Pixels::U8($slice) => $body,
+            $crate::raster::Pixels::U16($slice) => $body,
+            $crate::raster::Pixels::I16($slice) => $body,
+            $crate::raster::Pixels::U32($slice) => $body,
+            $crate::raster::Pixels::I32($slice) => $body,
+        }
+    };
+}
+
+impl VirtualMem<'_> {
+    /// Returns the band as an array of its type `data_type`.
+    pub fn pixels(&mut self, data_type: GDALDataType::Type) -> Result<Pixels<'_>, Box<dyn Error>> {
+        Ok(match data_type {
+            GDALDataType::GDT_Byte => Pixels::U8(self.as_mut_slice()),
+            GDALDataType::GDT_UInt16 => Pixels::U16(self.as_mut_slice()),
+            GDALDataType::GDT_Int16 => Pixels::I16(self.as_mut_slice()),
+            GDALDataType::GDT_UInt32 => Pixels::U32(self.as_mut_slice()),
+            GDALDataType::GDT_Int32 => Pixels::I32(self.as_mut_slice()),
+            _ => return Err(format!("unsupported pixel type {data_type}").into()),
+        })
+    }
+}
+
+/// Sets `mask` to 1 on the pixels of `src` in the mask, and to 0 elsewhere.
+pub fn to_mask<T: Pixel>(
+    src: &[T],
+    mask: &mut [u8],
+    width: usize,
+    height: usize,
+    tile_width: usize,
+    tile_height: usize,
+) {
+    for (win_x, win_y, win_w, win_h) in tiles::windows(width, height, tile_width, tile_height) {
+        for i in win_y..win_y + win_h {
+            for j in win_x..win_x + win_w {
+                mask[i * width + j] = src[i * width + j].is_set() as u8;
+            }
+        }
+    }
+}
+
+/// Clears the pixels of `dst` that are not set in `mask`, the others keeping their value.
+pub fn apply_mask<T: Pixel>(
+    dst: &mut [T],
+    mask: &[u8],
+    width: usize,
+    height: usize,
+    tile_width: usize,
+    tile_height: usize,
+) {
+    for (win_x, win_y, win_w, win_h) in tiles::windows(width, height, tile_width, tile_height) {
+        for i in win_y..win_y + win_h {
+            for j in win_x..win_x + win_w {
+                if mask[i * width + j] & 1 == 0 {
+                    dst[i * width + j] = T::default();
+                }
+            }
+        }
+    }
+}
+
+impl Drop for VirtualMem<'_> {
+    fn drop(&mut self) {
+        unsafe { gdal_sys::CPLVirtualMemFree(self.mem) };
+    }
+}
+
+/// A zero-filled temporary file mapped into memory, for buffers too large to keep in RAM.
+/// The file is removed on drop. An existing file isn't overwritten, as another run might
+/// be using it, but named in the error, e.g. when left behind by a run that was killed.
+pub struct ScratchMem {
+    path: PathBuf,
+    mmap: MmapMut,
+}
+
+impl ScratchMem {
+    pub fn new(path: impl Into<PathBuf>, len: usize) -> Result<Self, Box<dyn Error>> {
+        let path = path.into();
+        let file = match OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)
+        {
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                return Err(format!(
+                    "the scratch file {} already exists, remove it if no other run is using it",
+                    path.display()
+                )
+                .into())
+            }
+            file => file?,
+        };
+        match file
+            .set_len(len as u64)
+            .and_then(|()| unsafe { MmapMut::map_mut(&file) })
+        {
+            Ok(mmap) => Ok(Self { path, mmap }),
+            Err(e) => {
+                let _ = fs::remove_file(&path);
+                Err(e.into())
+            }
+        }
+    }
+
+    /// Returns the buffer as an array of `T`, which must be a plain integer type.
+    pub fn as_mut_slice<T>(&mut self) -> &mut [T] {
+        let data = self.mmap.as_mut_ptr() as *mut T;
+        let len = self.mmap.len() / mem::size_of::<T>();
+        assert_eq!(data as usize % mem::align_of::<T>(), 0);
+        unsafe { slice::from_raw_parts_mut(data, len) }
+    }
+}
+
+impl Drop for ScratchMem {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Creates a GeoTIFF with the size, block layout and georeferencing of `ds`.
+/// The bands are not interleaved, so each of them can be mapped with [`VirtualMem`].
+pub fn create_like<T: GdalType>(
+    ds: &Dataset,
+    path: &str,
+    block_size: (usize, usize),
+    bands: usize,
+) -> Result<Dataset, Box<dyn Error>> {
+    let (width, height) = ds.raster_size();
+    let block_x = block_size.0.to_string();
+    let block_y = block_size.1.to_string();
+    let mut options = vec![
+        RasterCreationOption {
+            key: "BIGTIFF",
+            value: "IF_SAFER",
+        },
+        RasterCreationOption {
+            key: "INTERLEAVE",
+            value: "BAND",
+        },
+    ];
+    if block_size.0 < width {
+        options.extend([
+            RasterCreationOption {
+                key: "TILED",
+                value: "YES",
+            },
+            RasterCreationOption {
+                key: "BLOCKXSIZE",
+                value: &block_x,
+            },
+            RasterCreationOption {
+                key: "BLOCKYSIZE",
+                value: &block_y,
+            },
+        ]);
+    }
+
+    let driver = Driver::get_by_name("GTiff")?;
+    let mut out = driver.create_with_band_type_with_options::<T, _>(
+        path,
+        width as isize,
+        height as isize,
+        bands as isize,
+        &options,
+    )?;
+    if let Ok(gt) = ds.geo_transform() {
+        out.set_geo_transform(&gt)?;
+    }
+    out.set_projection(&ds.projection())?;
+    Ok(out)
+}
+
+/// Sets a colour table with the given RGBA entries on `band`.
+pub fn set_palette(band: &mut RasterBand, colors: &[[i16; 4]]) {
+    let mut table = ColorTable::new(PaletteInterpretation::Rgba);
+    for (i, &[r, g, b, a]) in colors.iter().enumerate() {
+        table.set_color_entry(i as u16, &ColorEntry::rgba(r, g, b, a));
+    }
+    band.set_color_table(&table);
+}
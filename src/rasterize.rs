@@ -0,0 +1,226 @@
+use std::{
+    error::Error,
+    fs::File,
+    io::{BufWriter, Write},
+};
+
+use indicatif::ProgressBar;
+
+use crate::{region::Region, tiles};
+
+//================================
+// ROUND TRIP
+//================================
+// To see how faithful the traced polylines are, they're burnt back into a
+// raster on the grid of the skeleton, in pixel coordinates. Thin polylines
+// set the pixels their segments step through, one per row or column, which is
+// enough to give back the pixels of an exact trace. Each polyline can have its
+// own width, e.g. from an attribute; for the wider ones, the pixels whose
+// centre is within half of it from a segment are set instead, as a buffer
+// around them. Comparing the two rasters counts the skeleton pixels the
+// polylines missed, and the pixels they added.
+
+/// Pixel counts of the comparison between a skeleton and its burnt polylines.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Comparison {
+    pub skeleton: u64,
+    pub burnt: u64,
+    /// Skeleton pixels not burnt.
+    pub missed: u64,
+    /// Burnt pixels not on the skeleton.
+    pub added: u64,
+}
+
+fn burn_segment(out: &mut [u8], w: usize, h: usize, a: [f64; 2], b: [f64; 2], width: f64) {
+    let mut set = |x: f64, y: f64| {
+        if x >= 0.0 && y >= 0.0 && x < w as f64 && y < h as f64 {
+            out[y as usize * w + x as usize] = 1;
+        }
+    };
+    let (dx, dy) = (b[0] - a[0], b[1] - a[1]);
+
+    if width <= 1.0 {
+        let steps = dx.abs().max(dy.abs()).ceil().max(1.0) as usize;
+        for k in 0..=steps {
+            let t = k as f64 / steps as f64;
+            set((a[0] + t * dx).round(), (a[1] + t * dy).round());
+        }
+        return;
+    }
+
+    let r = width / 2.0;
+    let len2 = dx * dx + dy * dy;
+    let min_x = (a[0].min(b[0]) - r).ceil().max(0.0);
+    let min_y = (a[1].min(b[1]) - r).ceil().max(0.0);
+    let max_x = (a[0].max(b[0]) + r).floor().min(w as f64 - 1.0);
+    let max_y = (a[1].max(b[1]) + r).floor().min(h as f64 - 1.0);
+    let mut y = min_y;
+    while y <= max_y {
+        let mut x = min_x;
+        while x <= max_x {
+            // distance to the closest point of the segment
+            let t = if len2 > 0.0 {
+                (((x - a[0]) * dx + (y - a[1]) * dy) / len2).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+            let (ex, ey) = (a[0] + t * dx - x, a[1] + t * dy - y);
+            if ex * ex + ey * ey <= r * r {
+                set(x, y);
+            }
+            x += 1.0;
+        }
+        y += 1.0;
+    }
+}
+
+/// Sets to 1 the pixels of `out` under the `polylines`, in pixel coordinates,
+/// each burnt as wide as the pixels given at the same index of `widths`.
+pub fn burn_polylines(
+    out: &mut [u8],
+    width: usize,
+    height: usize,
+    polylines: &[Vec<[f64; 2]>],
+    widths: &[f64],
+) {
+    assert_eq!(polylines.len(), widths.len());
+    log::info!("Starting rasterization");
+    let pb = ProgressBar::new(polylines.len() as u64).with_message("Starting rasterization");
+    for (polyline, &w) in polylines.iter().zip(widths) {
+        match polyline.as_slice() {
+            [] => {}
+            [p] => burn_segment(out, width, height, *p, *p, w),
+            _ => {
+                for s in polyline.windows(2) {
+                    burn_segment(out, width, height, s[0], s[1], w);
+                }
+            }
+        }
+        pb.inc(1);
+    }
+    pb.finish();
+}
+
+/// Compares the thinned image `im` with the `burnt` polylines inside `region`,
+/// one tile at a time.
+pub fn compare(
+    im: &[u8],
+    burnt: &[u8],
+    width: usize,
+    height: usize,
+    tile_width: usize,
+    tile_height: usize,
+    region: &Region,
+) -> Comparison {
+    let ntx = width.div_ceil(tile_width);
+    let nty = height.div_ceil(tile_height);
+    let mut comparison = Comparison::default();
+
+    log::info!("Starting comparison");
+    let pb = ProgressBar::new((ntx * nty) as u64).with_message("Starting comparison");
+    for (win_x, win_y, win_w, win_h) in tiles::windows(width, height, tile_width, tile_height) {
+        for i in win_y..win_y + win_h {
+            for j in win_x..win_x + win_w {
+                if !region.contains(j, i) {
+                    continue;
+                }
+                let skeleton = im[i * width + j] & 1 != 0;
+                let burnt = burnt[i * width + j] != 0;
+                comparison.skeleton += skeleton as u64;
+                comparison.burnt += burnt as u64;
+                comparison.missed += (skeleton && !burnt) as u64;
+                comparison.added += (burnt && !skeleton) as u64;
+            }
+        }
+        pb.inc(1);
+    }
+    pb.finish();
+
+    log::info!(
+        "The polylines cover {} of the {} skeleton pixels, missing {} and adding {}",
+        comparison.skeleton - comparison.missed,
+        comparison.skeleton,
+        comparison.missed,
+        comparison.added
+    );
+    comparison
+}
+
+/// Writes the comparison as a single-row CSV table.
+pub fn write_comparison(path: &str, comparison: &Comparison) -> Result<(), Box<dyn Error>> {
+    let mut out = BufWriter::new(File::create(path)?);
+    writeln!(out, "skeleton_pixels,burnt_pixels,missed,added")?;
+    writeln!(
+        out,
+        "{},{},{},{}",
+        comparison.skeleton, comparison.burnt, comparison.missed, comparison.added
+    )?;
+    out.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_util::{random_mask, Rng},
+        thinning_zs, walk,
+    };
+
+    #[test]
+    fn exact_trace_round_trips() {
+        let mut rng = Rng(0x6a09_e667_f3bc_c908);
+        for _ in 0..200 {
+            let (w, h) = (1 + rng.below(40), 1 + rng.below(40));
+            let mut im = random_mask(&mut rng, w, h);
+            thinning_zs(&mut im, w, h);
+            // isolated pixels have no lines to trace
+            let isolated = (0..w * h)
+                .filter(|&p| {
+                    let (i, j) = (p / w, p % w);
+                    im[p] != 0
+                        && (i.saturating_sub(1)..(i + 2).min(h)).all(|y| {
+                            (j.saturating_sub(1)..(j + 2).min(w))
+                                .all(|x| (x, y) == (j, i) || im[y * w + x] == 0)
+                        })
+                })
+                .collect::<Vec<_>>();
+            for p in isolated {
+                im[p] = 0;
+            }
+
+            let polylines = walk::trace_pixels(&im, w, h, 0, 0, w, h)
+                .into_iter()
+                .map(|polyline| {
+                    polyline
+                        .into_iter()
+                        .map(|[x, y]| [x as f64, y as f64])
+                        .collect()
+                })
+                .collect::<Vec<_>>();
+            let mut burnt = vec![0; w * h];
+            burn_polylines(&mut burnt, w, h, &polylines, &vec![1.0; polylines.len()]);
+            let comparison = compare(&im, &burnt, w, h, 8, 8, &Region::full(w, h));
+            assert_eq!(comparison.missed, 0, "{w}x{h}");
+            assert_eq!(comparison.added, 0, "{w}x{h}");
+            assert_eq!(comparison.burnt, comparison.skeleton);
+        }
+    }
+    #[test]
+    fn burns_each_polyline_with_its_width() {
+        let (w, h) = (20, 12);
+        let polylines = vec![vec![[2.0, 3.0], [17.0, 3.0]], vec![[2.0, 8.0], [17.0, 8.0]]];
+        let mut burnt = vec![0; w * h];
+        burn_polylines(&mut burnt, w, h, &polylines, &[1.0, 3.0]);
+        let rows = (0..h)
+            .map(|i| {
+                burnt[i * w..(i + 1) * w]
+                    .iter()
+                    .filter(|&&p| p != 0)
+                    .count()
+            })
+            .collect::<Vec<_>>();
+        // the thin line is a pixel high, the wide one three, rounded at the ends
+        assert_eq!(rows, [0, 0, 0, 16, 0, 0, 0, 18, 18, 18, 0, 0]);
+    }
+}
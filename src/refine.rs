@@ -0,0 +1,137 @@
+//================================
+// SUB-PIXEL REFINEMENT
+//================================
+// The tracer places its vertices on integer pixels, averaged stroke edges and
+// chunk centres, so the polylines wobble at chunk scale. Each vertex is moved
+// onto the line fitted by least squares to the skeleton pixels around it.
+// Vertices around which the skeleton doesn't look like a line (junctions,
+// sharp bends) are kept in place. The result only depends on the position of
+// the vertex, so vertices shared between polylines stay shared.
+
+// largest ratio between the two eigenvalues of the covariance of the pixels
+// for them to be considered a line
+const MAX_ANISOTROPY: f64 = 0.1;
+
+fn refine_vertex(im: &[u8], w: usize, h: usize, x: usize, y: usize, radius: usize) -> [f64; 2] {
+    let vertex = [x as f64, y as f64];
+    let min_x = x.saturating_sub(radius);
+    let max_x = (x + radius).min(w - 1);
+    let min_y = y.saturating_sub(radius);
+    let max_y = (y + radius).min(h - 1);
+
+    let mut n = 0.0;
+    let (mut sx, mut sy) = (0.0, 0.0);
+    let (mut sxx, mut syy, mut sxy) = (0.0, 0.0, 0.0);
+    for i in min_y..=max_y {
+        for j in min_x..=max_x {
+            if im[i * w + j] & 1 == 0 {
+                continue;
+            }
+            let (px, py) = (j as f64, i as f64);
+            n += 1.0;
+            sx += px;
+            sy += py;
+            sxx += px * px;
+            syy += py * py;
+            sxy += px * py;
+        }
+    }
+    if n < 3.0 {
+        return vertex;
+    }
+
+    let (mx, my) = (sx / n, sy / n);
+    let cxx = sxx / n - mx * mx;
+    let cyy = syy / n - my * my;
+    let cxy = sxy / n - mx * my;
+
+    // eigenvalues of the covariance matrix
+    let trace = cxx + cyy;
+    let det = cxx * cyy - cxy * cxy;
+    let disc = (trace * trace / 4.0 - det).max(0.0).sqrt();
+    let l1 = trace / 2.0 + disc;
+    let l2 = trace / 2.0 - disc;
+    if l1 <= 0.0 || l2 > MAX_ANISOTROPY * l1 {
+        return vertex;
+    }
+
+    // direction of the line, the eigenvector of the largest eigenvalue
+    let (dx, dy) = if cxy.abs() > f64::EPSILON {
+        (l1 - cyy, cxy)
+    } else if cxx >= cyy {
+        (1.0, 0.0)
+    } else {
+        (0.0, 1.0)
+    };
+    let norm = (dx * dx + dy * dy).sqrt();
+    let (dx, dy) = (dx / norm, dy / norm);
+
+    let t = (vertex[0] - mx) * dx + (vertex[1] - my) * dy;
+    let refined = [mx + t * dx, my + t * dy];
+    // don't move the vertex out of its pixel neighbourhood
+    if (refined[0] - vertex[0]).abs() > 1.0 || (refined[1] - vertex[1]).abs() > 1.0 {
+        return vertex;
+    }
+    refined
+}
+
+/// Moves the vertices of the traced polylines to sub-pixel positions, looking at
+/// the skeleton pixels of the thinned image `im` within `radius` of each vertex.
+pub fn refine_polylines(
+    im: &[u8],
+    width: usize,
+    height: usize,
+    polylines: &[Vec<[usize; 2]>],
+    radius: usize,
+) -> Vec<Vec<[f64; 2]>> {
+    polylines
+        .iter()
+        .map(|polyline| {
+            polyline
+                .iter()
+                .map(|p| refine_vertex(im, width, height, p[0], p[1], radius))
+                .collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const W: usize = 21;
+
+    fn assert_close(actual: [f64; 2], expected: [f64; 2]) {
+        assert!(
+            (actual[0] - expected[0]).abs() < 1e-9 && (actual[1] - expected[1]).abs() < 1e-9,
+            "{actual:?} != {expected:?}"
+        );
+    }
+
+    #[test]
+    fn moves_vertices_onto_a_diagonal() {
+        let mut im = vec![0; W * W];
+        for i in 0..W {
+            im[i * W + i] = 1;
+        }
+        let polylines = vec![vec![[5, 5], [11, 10], [15, 15]]];
+        let refined = refine_polylines(&im, W, W, &polylines, 3);
+        // the vertex next to the line goes to the closest point on it
+        assert_close(refined[0][0], [5.0, 5.0]);
+        assert_close(refined[0][1], [10.5, 10.5]);
+        assert_close(refined[0][2], [15.0, 15.0]);
+    }
+
+    #[test]
+    fn keeps_junctions_in_place() {
+        let mut im = vec![0; W * W];
+        for k in 0..W {
+            im[10 * W + k] = 1;
+            im[k * W + 10] = 1;
+        }
+        let polylines = vec![vec![[10, 10], [11, 9]]];
+        let refined = refine_polylines(&im, W, W, &polylines, 3);
+        assert_close(refined[0][0], [10.0, 10.0]);
+        assert_close(refined[0][1], [11.0, 9.0]);
+    }
+}
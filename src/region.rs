@@ -0,0 +1,563 @@
+#[cfg(feature = "gdal")]
+use std::error::Error;
+use std::str::FromStr;
+
+#[cfg(feature = "gdal")]
+use gdal::{
+    raster::{rasterize, RasterBand},
+    spatial_ref::CoordTransform,
+    vector::{Geometry, LayerAccess},
+    Dataset, Driver, GeoTransform,
+};
+#[cfg(feature = "gdal")]
+use gdal_sys::{GDALDataType, OSRAxisMappingStrategy};
+
+#[cfg(feature = "gdal")]
+use crate::raster::Pixel;
+use crate::tiles::Rect;
+
+//================================
+// REGION OF INTEREST
+//================================
+// The thinning and the tracing can be restricted to a pixel window, to a
+// bounding box in map coordinates or to the polygons of a cutline. Pixels
+// outside the region are never removed, but the pixels inside still see them
+// as neighbours, so the skeleton inside joins up with the (already thinned)
+// raster around it.
+//
+// Past the edges of the image, the pixels are seen according to the border
+// policy: as background, as copies of the closest image pixel, or read from a
+// larger dataset around the image, like the neighbouring tiles of a mosaic.
+// Like the pixels outside the region, the ones read from it never change.
+
+/// How the region of interest was given on the command line.
+#[derive(Clone, Debug, PartialEq)]
+pub enum RegionSpec {
+    /// `x,y,w,h` in pixels.
+    Window([usize; 4]),
+    /// `min_x,min_y,max_x,max_y` in the coordinates of the raster.
+    Bbox([f64; 4]),
+    /// Vector file with the polygons to process.
+    Cutline(String),
+}
+
+fn parse_list<T: FromStr, const N: usize>(s: &str) -> Option<[T; N]> {
+    let values = s
+        .split(',')
+        .map(|v| v.trim().parse().ok())
+        .collect::<Option<Vec<T>>>()?;
+    values.try_into().ok()
+}
+
+impl RegionSpec {
+    pub fn parse_window(s: &str) -> Result<Self, String> {
+        parse_list(s)
+            .map(RegionSpec::Window)
+            .ok_or_else(|| format!("invalid window {s}, expected x,y,w,h"))
+    }
+
+    pub fn parse_bbox(s: &str) -> Result<Self, String> {
+        parse_list(s)
+            .map(RegionSpec::Bbox)
+            .ok_or_else(|| format!("invalid bounding box {s}, expected min_x,min_y,max_x,max_y"))
+    }
+}
+
+/// How the pixels past the edges of the image were given on the command line.
+#[derive(Clone, Debug, PartialEq)]
+pub enum BorderSpec {
+    Background,
+    /// Copies of the closest image pixel, see `Border::Replicate`.
+    Replicate,
+    /// Raster on the same grid and of the same pixel type, covering at least a
+    /// pixel around the image.
+    Overlap(String),
+}
+
+/// The pixels one step past the edges of the image, from its top-left corner
+/// at -1, -1 to its bottom-right one at `width`, `height`, 1 where set.
+#[derive(Clone, Debug)]
+pub struct Frame {
+    /// `width + 2` pixels above and below the image, from the left corner.
+    pub top: Vec<u8>,
+    pub bottom: Vec<u8>,
+    /// `height` pixels on each side of the image.
+    pub left: Vec<u8>,
+    pub right: Vec<u8>,
+}
+
+/// How the thinning sees the pixels past the edges of the image.
+#[derive(Clone, Debug)]
+pub enum Border {
+    /// They're background, so the objects touching the edges are thinned from there too.
+    Background,
+    /// They're copies of the closest image pixel, so the objects crossing the
+    /// edges look like they go on. The copies follow the thinning of the image,
+    /// so an object filling a corner of the image can be thinned away entirely,
+    /// as if its skeleton was past the corner.
+    Replicate,
+    /// They're given by a frame around the image.
+    Overlap(Frame),
+}
+
+pub struct Region {
+    pub rect: Rect,
+    // one byte per pixel of `rect`, non-zero inside the cutline
+    mask: Option<Vec<u8>>,
+    pub border: Border,
+}
+
+#[cfg(feature = "gdal")]
+fn map_to_pixel(gt: &GeoTransform, x: f64, y: f64) -> (f64, f64) {
+    let det = gt[1] * gt[5] - gt[2] * gt[4];
+    let dx = x - gt[0];
+    let dy = y - gt[3];
+    (
+        (dx * gt[5] - dy * gt[2]) / det,
+        (dy * gt[1] - dx * gt[4]) / det,
+    )
+}
+
+/// Pixels covering the map rectangle, clipped to the image.
+#[cfg(feature = "gdal")]
+fn bbox_to_rect(gt: &GeoTransform, bbox: [f64; 4], width: usize, height: usize) -> Rect {
+    let [min_x, min_y, max_x, max_y] = bbox;
+    let corners = [
+        map_to_pixel(gt, min_x, min_y),
+        map_to_pixel(gt, min_x, max_y),
+        map_to_pixel(gt, max_x, min_y),
+        map_to_pixel(gt, max_x, max_y),
+    ];
+    let x0 = corners.iter().map(|c| c.0).fold(f64::INFINITY, f64::min);
+    let y0 = corners.iter().map(|c| c.1).fold(f64::INFINITY, f64::min);
+    let x1 = corners
+        .iter()
+        .map(|c| c.0)
+        .fold(f64::NEG_INFINITY, f64::max);
+    let y1 = corners
+        .iter()
+        .map(|c| c.1)
+        .fold(f64::NEG_INFINITY, f64::max);
+    let clamp = |v: f64, max: usize| v.max(0.0).min(max as f64) as usize;
+    Rect {
+        x0: clamp(x0.floor(), width),
+        y0: clamp(y0.floor(), height),
+        x1: clamp(x1.ceil(), width),
+        y1: clamp(y1.ceil(), height),
+    }
+}
+
+/// The mask bits of a window of `band`, set like the pixels of the input.
+#[cfg(feature = "gdal")]
+fn read_mask(
+    band: &RasterBand,
+    offset: (isize, isize),
+    size: (usize, usize),
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    fn read<T: Pixel>(
+        band: &RasterBand,
+        offset: (isize, isize),
+        size: (usize, usize),
+    ) -> Result<Vec<u8>, Box<dyn Error>> {
+        let mut window = vec![T::default(); size.0 * size.1];
+        band.read_into_slice(offset, size, size, &mut window, None)?;
+        Ok(window.into_iter().map(|v| v.is_set() as u8).collect())
+    }
+    match band.band_type() {
+        GDALDataType::GDT_Byte => read::<u8>(band, offset, size),
+        GDALDataType::GDT_UInt16 => read::<u16>(band, offset, size),
+        GDALDataType::GDT_Int16 => read::<i16>(band, offset, size),
+        GDALDataType::GDT_UInt32 => read::<u32>(band, offset, size),
+        GDALDataType::GDT_Int32 => read::<i32>(band, offset, size),
+        data_type => Err(format!("unsupported pixel type {data_type}").into()),
+    }
+}
+
+impl Border {
+    #[cfg(feature = "gdal")]
+    pub fn new(
+        ds: &Dataset,
+        spec: &BorderSpec,
+        band: isize,
+        width: usize,
+        height: usize,
+    ) -> Result<Self, Box<dyn Error>> {
+        let path = match spec {
+            BorderSpec::Background => return Ok(Border::Background),
+            BorderSpec::Replicate => return Ok(Border::Replicate),
+            BorderSpec::Overlap(path) => path,
+        };
+        let gt = ds.geo_transform()?;
+        let overlap = Dataset::open(path)?;
+        let overlap_gt = overlap.geo_transform()?;
+        if [1, 2, 4, 5].iter().any(|&k| gt[k] != overlap_gt[k]) {
+            return Err(format!("{path} doesn't have the same resolution as the input").into());
+        }
+        // the top-left corner of the input in the pixels of the overlap
+        let (x0, y0) = map_to_pixel(&overlap_gt, gt[0], gt[3]);
+        if (x0 - x0.round()).abs() > 1e-6 || (y0 - y0.round()).abs() > 1e-6 {
+            return Err(format!("{path} isn't aligned with the pixels of the input").into());
+        }
+        let (x0, y0) = (x0.round() as isize, y0.round() as isize);
+        let input_type = ds.rasterband(band)?.band_type();
+        let band = overlap.rasterband(band)?;
+        if band.band_type() != input_type {
+            return Err(format!("{path} doesn't have the same pixel type as the input").into());
+        }
+        let (overlap_width, overlap_height) = band.size();
+        let (w, h) = (width as isize, height as isize);
+        if x0 < 1 || y0 < 1 || x0 + w >= overlap_width as isize || y0 + h >= overlap_height as isize
+        {
+            return Err(format!("{path} doesn't cover the pixels around the input").into());
+        }
+
+        Ok(Border::Overlap(Frame {
+            top: read_mask(&band, (x0 - 1, y0 - 1), (width + 2, 1))?,
+            bottom: read_mask(&band, (x0 - 1, y0 + h), (width + 2, 1))?,
+            left: read_mask(&band, (x0 - 1, y0), (1, height))?,
+            right: read_mask(&band, (x0 + w, y0), (1, height))?,
+        }))
+    }
+
+    /// The mask bit of the pixel at `x`, `y`, at most one pixel past the edges.
+    fn pixel(&self, im: &[u8], w: usize, h: usize, x: isize, y: isize) -> u8 {
+        let (iw, ih) = (w as isize, h as isize);
+        if x >= 0 && y >= 0 && x < iw && y < ih {
+            return im[y as usize * w + x as usize] & 1;
+        }
+        match self {
+            Border::Background => 0,
+            Border::Replicate => {
+                let x = x.clamp(0, iw - 1) as usize;
+                let y = y.clamp(0, ih - 1) as usize;
+                im[y * w + x] & 1
+            }
+            Border::Overlap(frame) => {
+                if y < 0 {
+                    frame.top[(x + 1) as usize]
+                } else if y >= ih {
+                    frame.bottom[(x + 1) as usize]
+                } else if x < 0 {
+                    frame.left[y as usize]
+                } else {
+                    frame.right[y as usize]
+                }
+            }
+        }
+    }
+
+    /// The neighbours `p2..p9` of the pixel at `x`, `y`, clockwise from the top
+    /// as in the thinning.
+    pub(crate) fn neighbours(&self, im: &[u8], w: usize, h: usize, x: usize, y: usize) -> [u8; 8] {
+        let (x, y) = (x as isize, y as isize);
+        [
+            self.pixel(im, w, h, x, y - 1),
+            self.pixel(im, w, h, x + 1, y - 1),
+            self.pixel(im, w, h, x + 1, y),
+            self.pixel(im, w, h, x + 1, y + 1),
+            self.pixel(im, w, h, x, y + 1),
+            self.pixel(im, w, h, x - 1, y + 1),
+            self.pixel(im, w, h, x - 1, y),
+            self.pixel(im, w, h, x - 1, y - 1),
+        ]
+    }
+}
+
+impl Region {
+    /// The whole image, with a background border.
+    pub fn full(width: usize, height: usize) -> Self {
+        Self {
+            rect: Rect::new(0, 0, width, height),
+            mask: None,
+            border: Border::Background,
+        }
+    }
+
+    pub fn with_border(self, border: Border) -> Self {
+        Self { border, ..self }
+    }
+
+    #[cfg(feature = "gdal")]
+    pub fn new(
+        ds: &Dataset,
+        spec: &RegionSpec,
+        width: usize,
+        height: usize,
+    ) -> Result<Self, Box<dyn Error>> {
+        let region = match spec {
+            RegionSpec::Window([x, y, w, h]) => Self {
+                rect: Rect::new(*x, *y, *w, *h).intersect(&Rect::new(0, 0, width, height)),
+                mask: None,
+                border: Border::Background,
+            },
+            RegionSpec::Bbox(bbox) => Self {
+                rect: bbox_to_rect(&ds.geo_transform()?, *bbox, width, height),
+                mask: None,
+                border: Border::Background,
+            },
+            RegionSpec::Cutline(path) => Self::cutline(ds, path, width, height)?,
+        };
+        if region.rect.is_empty() {
+            return Err("the region of interest doesn't overlap the image".into());
+        }
+        log::info!(
+            "Restricting to {}x{} pixels at {}, {}",
+            region.rect.width(),
+            region.rect.height(),
+            region.rect.x0,
+            region.rect.y0
+        );
+        Ok(region)
+    }
+
+    #[cfg(feature = "gdal")]
+    fn cutline(
+        ds: &Dataset,
+        path: &str,
+        width: usize,
+        height: usize,
+    ) -> Result<Self, Box<dyn Error>> {
+        let gt = ds.geo_transform()?;
+        let cutline = Dataset::open(path)?;
+        let mut layer = cutline.layer(0)?;
+
+        // bring the polygons to the CRS of the raster
+        let transform = match (layer.spatial_ref(), ds.spatial_ref().ok()) {
+            (Some(source), Some(target)) => {
+                source
+                    .set_axis_mapping_strategy(OSRAxisMappingStrategy::OAMS_TRADITIONAL_GIS_ORDER);
+                target
+                    .set_axis_mapping_strategy(OSRAxisMappingStrategy::OAMS_TRADITIONAL_GIS_ORDER);
+                Some(CoordTransform::new(&source, &target)?)
+            }
+            _ => None,
+        };
+        let mut geometries = Vec::new();
+        let mut bbox = [
+            f64::INFINITY,
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+            f64::NEG_INFINITY,
+        ];
+        for feature in layer.features() {
+            let geometry: Geometry = match (feature.geometry(), &transform) {
+                (Some(geometry), Some(ct)) => geometry.transform(ct)?,
+                (Some(geometry), None) => geometry.clone(),
+                (None, _) => continue,
+            };
+            let envelope = geometry.envelope();
+            bbox[0] = bbox[0].min(envelope.MinX);
+            bbox[1] = bbox[1].min(envelope.MinY);
+            bbox[2] = bbox[2].max(envelope.MaxX);
+            bbox[3] = bbox[3].max(envelope.MaxY);
+            geometries.push(geometry);
+        }
+        if geometries.is_empty() {
+            return Err(format!("no polygons in {path}").into());
+        }
+
+        let rect = bbox_to_rect(&gt, bbox, width, height);
+        if rect.is_empty() {
+            return Ok(Self {
+                rect,
+                mask: None,
+                border: Border::Background,
+            });
+        }
+
+        // burn the polygons into a raster covering `rect`
+        let driver = Driver::get_by_name("MEM")?;
+        let mut mem = driver.create_with_band_type::<u8, _>(
+            "",
+            rect.width() as isize,
+            rect.height() as isize,
+            1,
+        )?;
+        let (x0, y0) = (rect.x0 as f64, rect.y0 as f64);
+        mem.set_geo_transform(&[
+            gt[0] + x0 * gt[1] + y0 * gt[2],
+            gt[1],
+            gt[2],
+            gt[3] + x0 * gt[4] + y0 * gt[5],
+            gt[4],
+            gt[5],
+        ])?;
+        let burn_values = vec![1.0; geometries.len()];
+        rasterize(&mut mem, &[1], &geometries, &burn_values, None)?;
+        let mut mask = vec![0; rect.width() * rect.height()];
+        mem.rasterband(1)?.read_into_slice(
+            (0, 0),
+            (rect.width(), rect.height()),
+            (rect.width(), rect.height()),
+            &mut mask,
+            None,
+        )?;
+
+        Ok(Self {
+            rect,
+            mask: Some(mask),
+            border: Border::Background,
+        })
+    }
+
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        let r = &self.rect;
+        if x < r.x0 || x >= r.x1 || y < r.y0 || y >= r.y1 {
+            return false;
+        }
+        match &self.mask {
+            Some(mask) => mask[(y - r.y0) * r.width() + x - r.x0] != 0,
+            None => true,
+        }
+    }
+
+    /// Clears the removal marks of the pixels of `r` outside the cutline.
+    pub fn unmark_outside(&self, im: &mut [u8], width: usize, r: &Rect) {
+        if self.mask.is_none() {
+            return;
+        }
+        for i in r.y0..r.y1 {
+            for j in r.x0..r.x1 {
+                if im[i * width + j] & 2 != 0 && !self.contains(j, i) {
+                    im[i * width + j] &= 1;
+                }
+            }
+        }
+    }
+
+    /// Splits the polylines at the vertices outside the cutline.
+    pub fn clip_polylines(&self, polylines: Vec<Vec<[usize; 2]>>) -> Vec<Vec<[usize; 2]>> {
+        if self.mask.is_none() {
+            return polylines;
+        }
+        let mut clipped = Vec::new();
+        for polyline in polylines {
+            let mut part = Vec::new();
+            for p in polyline {
+                if self.contains(p[0], p[1]) {
+                    part.push(p);
+                } else {
+                    if part.len() > 1 {
+                        clipped.push(part);
+                    }
+                    part = Vec::new();
+                }
+            }
+            if part.len() > 1 {
+                clipped.push(part);
+            }
+        }
+        clipped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_windows_and_bboxes() {
+        assert_eq!(
+            RegionSpec::parse_window("1, 2,3,4"),
+            Ok(RegionSpec::Window([1, 2, 3, 4]))
+        );
+        assert_eq!(
+            RegionSpec::parse_bbox("-1.5,2,3,4e3"),
+            Ok(RegionSpec::Bbox([-1.5, 2.0, 3.0, 4000.0]))
+        );
+        assert!(RegionSpec::parse_window("1,2,3").is_err());
+        assert!(RegionSpec::parse_window("1,2,3,-4").is_err());
+        assert!(RegionSpec::parse_bbox("1,2,3,4,5").is_err());
+    }
+
+    #[cfg(feature = "gdal")]
+    #[test]
+    fn clips_bboxes_to_the_image() {
+        // 2 units per pixel, north up
+        let gt = [100.0, 2.0, 0.0, 200.0, 0.0, -2.0];
+        let rect = |bbox| bbox_to_rect(&gt, bbox, 10, 10);
+        assert_eq!(rect([104.0, 186.0, 110.0, 196.0]), Rect::new(2, 2, 3, 5));
+        // partial pixels are included
+        assert_eq!(rect([105.0, 185.0, 109.0, 195.0]), Rect::new(2, 2, 3, 6));
+        assert_eq!(rect([90.0, 150.0, 105.0, 199.0]), Rect::new(0, 0, 3, 10));
+        assert!(rect([130.0, 150.0, 140.0, 160.0]).is_empty());
+    }
+
+    /// A 3x3 cutline at 2, 1, without its centre pixel.
+    fn ring_region() -> Region {
+        Region {
+            rect: Rect::new(2, 1, 3, 3),
+            mask: Some(vec![1, 1, 1, 1, 0, 1, 1, 1, 1]),
+            border: Border::Background,
+        }
+    }
+
+    #[test]
+    fn checks_the_window_and_the_cutline() {
+        let region = ring_region();
+        assert!(region.contains(2, 1));
+        assert!(region.contains(4, 3));
+        assert!(!region.contains(3, 2));
+        assert!(!region.contains(1, 1));
+        assert!(!region.contains(5, 2));
+        assert!(!region.contains(2, 4));
+
+        let window = Region {
+            mask: None,
+            ..ring_region()
+        };
+        assert!(window.contains(3, 2));
+        assert!(!window.contains(5, 2));
+    }
+
+    #[test]
+    fn splits_polylines_outside_the_cutline() {
+        let polylines = vec![
+            vec![[0, 1], [2, 1], [3, 1], [3, 2], [3, 3], [4, 3]],
+            vec![[2, 2], [3, 2], [4, 2]],
+            vec![[5, 0], [6, 0]],
+        ];
+        assert_eq!(
+            ring_region().clip_polylines(polylines.clone()),
+            [vec![[2, 1], [3, 1]], vec![[3, 3], [4, 3]]]
+        );
+        let window = Region::full(7, 5);
+        assert_eq!(window.clip_polylines(polylines.clone()), polylines);
+    }
+
+    #[test]
+    fn unmarks_the_pixels_outside_the_cutline() {
+        let (w, h) = (6, 5);
+        let mut im = vec![3; w * h];
+        ring_region().unmark_outside(&mut im, w, &Rect::new(0, 0, w, h));
+        for i in 0..h {
+            for j in 0..w {
+                let expected = if ring_region().contains(j, i) { 3 } else { 1 };
+                assert_eq!(im[i * w + j], expected, "({j}, {i})");
+            }
+        }
+    }
+
+    #[test]
+    fn sees_past_the_edges() {
+        let (w, h) = (3, 2);
+        let im = [1, 0, 0, 0, 0, 1];
+        let replicate = Border::Replicate;
+        assert_eq!(replicate.pixel(&im, w, h, -1, -1), 1);
+        assert_eq!(replicate.pixel(&im, w, h, 3, 2), 1);
+        assert_eq!(replicate.pixel(&im, w, h, 3, -1), 0);
+        assert_eq!(Border::Background.pixel(&im, w, h, -1, -1), 0);
+
+        let overlap = Border::Overlap(Frame {
+            top: vec![1, 0, 0, 0, 0],
+            bottom: vec![0, 0, 0, 0, 1],
+            left: vec![0, 1],
+            right: vec![1, 0],
+        });
+        assert_eq!(overlap.pixel(&im, w, h, -1, -1), 1);
+        assert_eq!(overlap.pixel(&im, w, h, 3, 2), 1);
+        assert_eq!(overlap.pixel(&im, w, h, -1, 1), 1);
+        assert_eq!(overlap.pixel(&im, w, h, 3, 0), 1);
+        assert_eq!(overlap.pixel(&im, w, h, 0, 2), 0);
+    }
+}
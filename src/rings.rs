@@ -0,0 +1,175 @@
+use std::collections::HashMap;
+
+//================================
+// RINGS
+//================================
+// A loop of the skeleton without junctions, like a lake shore, comes out of
+// the tracer as a polyline whose ends are next to each other but not joined,
+// or as several pieces meeting end to end. The ends of the polylines less than
+// a pixel apart are grouped into nodes. The cycles of polylines through nodes
+// where exactly two ends meet are joined into a single polyline, and the
+// polylines starting and ending at the same node, also the loops hanging off a
+// junction, are closed by repeating their first vertex.
+
+fn find(parent: &mut [usize], mut e: usize) -> usize {
+    while parent[e] != e {
+        parent[e] = parent[parent[e]];
+        e = parent[e];
+    }
+    e
+}
+
+/// Node of each polyline end (`2 * k` for the first vertex of the polyline `k`,
+/// `2 * k + 1` for the last one), as the smallest end in it.
+fn nodes(polylines: &[Vec<[usize; 2]>]) -> Vec<usize> {
+    let ends = polylines
+        .iter()
+        .flat_map(|polyline| [polyline[0], polyline[polyline.len() - 1]])
+        .collect::<Vec<_>>();
+    let mut at = HashMap::<[usize; 2], Vec<usize>>::new();
+    for (e, &p) in ends.iter().enumerate() {
+        at.entry(p).or_default().push(e);
+    }
+
+    let mut parent = (0..ends.len()).collect::<Vec<_>>();
+    for (e, &[x, y]) in ends.iter().enumerate() {
+        for ny in y.saturating_sub(1)..=y + 1 {
+            for nx in x.saturating_sub(1)..=x + 1 {
+                for &f in at.get(&[nx, ny]).into_iter().flatten() {
+                    let (a, b) = (find(&mut parent, e), find(&mut parent, f));
+                    parent[a.max(b)] = a.min(b);
+                }
+            }
+        }
+    }
+    (0..ends.len()).map(|e| find(&mut parent, e)).collect()
+}
+
+/// Joins the polylines forming a cycle without junctions and closes the loops,
+/// returning the polylines and whether each of them is a ring.
+pub fn close_rings(polylines: Vec<Vec<[usize; 2]>>) -> (Vec<Vec<[usize; 2]>>, Vec<bool>) {
+    let polylines = polylines
+        .into_iter()
+        .filter(|polyline| !polyline.is_empty())
+        .collect::<Vec<_>>();
+    let node = nodes(&polylines);
+    let mut members = HashMap::<usize, Vec<usize>>::new();
+    for (e, &n) in node.iter().enumerate() {
+        members.entry(n).or_default().push(e);
+    }
+    // the other end at a node where exactly two meet
+    let through = |e: usize| match members[&node[e]].as_slice() {
+        &[a, b] => Some(if a == e { b } else { a }),
+        _ => None,
+    };
+
+    // pieces joined into a ring, and pieces known not to be on a cycle
+    let mut joined_into = vec![false; polylines.len()];
+    let mut open = vec![false; polylines.len()];
+    let mut joined = Vec::new();
+    let mut rings = Vec::new();
+    for k in 0..polylines.len() {
+        if joined_into[k] {
+            continue;
+        }
+
+        // walk forward through the nodes of degree 2, until getting back to the
+        // first end of `k` or reaching a node that isn't one
+        let mut cycle = vec![(k, false)];
+        let mut exit = 2 * k + 1;
+        let closed = !open[k]
+            && loop {
+                match through(exit) {
+                    Some(e) if e == 2 * k => break true,
+                    Some(e) if !open[e / 2] => {
+                        // entering through the last vertex means going backwards
+                        let reversed = e % 2 == 1;
+                        cycle.push((e / 2, reversed));
+                        exit = if reversed { e - 1 } else { e + 1 };
+                    }
+                    _ => break false,
+                }
+            };
+
+        if closed && cycle.len() > 1 {
+            let mut ring: Vec<[usize; 2]> = Vec::new();
+            for (piece, reversed) in cycle {
+                joined_into[piece] = true;
+                let mut vertices = polylines[piece].clone();
+                if reversed {
+                    vertices.reverse();
+                }
+                if ring.last() == vertices.first() {
+                    vertices.remove(0);
+                }
+                ring.extend(vertices);
+            }
+            joined.push(ring);
+            rings.push(true);
+        } else {
+            for (piece, _) in cycle {
+                open[piece] = true;
+            }
+            joined.push(polylines[k].clone());
+            rings.push(node[2 * k] == node[2 * k + 1]);
+        }
+    }
+
+    for (polyline, ring) in joined.iter_mut().zip(&mut rings) {
+        // a short spur with both ends next to each other isn't a loop
+        if *ring && polyline.len() < 3 {
+            *ring = false;
+        }
+        if *ring && polyline.first() != polyline.last() {
+            polyline.push(polyline[0]);
+        }
+    }
+    (joined, rings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn joins_loop_split_into_pieces() {
+        // the outline of a square, in three pieces going different ways,
+        // and a line next to it
+        let top = vec![[2, 2], [3, 2], [4, 2], [5, 2], [6, 2]];
+        let bottom_right = vec![
+            [2, 6],
+            [3, 6],
+            [4, 6],
+            [5, 6],
+            [6, 6],
+            [6, 5],
+            [6, 4],
+            [6, 3],
+        ];
+        let left = vec![[2, 5], [2, 4], [2, 3]];
+        let line = vec![[10, 2], [10, 3], [10, 4]];
+
+        let (polylines, rings) = close_rings(vec![top, bottom_right, left, line.clone()]);
+        let square = vec![
+            [2, 2],
+            [3, 2],
+            [4, 2],
+            [5, 2],
+            [6, 2],
+            [6, 3],
+            [6, 4],
+            [6, 5],
+            [6, 6],
+            [5, 6],
+            [4, 6],
+            [3, 6],
+            [2, 6],
+            [2, 5],
+            [2, 4],
+            [2, 3],
+            [2, 2],
+        ];
+        assert_eq!(polylines, vec![square, line]);
+        assert_eq!(rings, vec![true, false]);
+    }
+}
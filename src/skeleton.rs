@@ -1,6 +1,23 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    mem,
+};
+
 const HORIZONTAL: u8 = 1;
 const VERTICAL: u8 = 2;
 
+// chunks smaller than this are traced on a single thread
+const PARALLEL_AREA: usize = 256 * 256;
+
+/// How the skeleton is turned into polylines.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Tracer {
+    /// `trace_skeleton`, approximating the skeleton with segments in small chunks.
+    Heuristic,
+    /// `walk::trace_pixels`, following the skeleton pixel by pixel.
+    Exact,
+}
+
 //================================
 // MAIN ALGORITHM
 //================================
@@ -9,76 +26,116 @@ const VERTICAL: u8 = 2;
 fn not_empty(im: &[u8], ww: usize, _hh: usize, x: usize, y: usize, w: usize, h: usize) -> bool {
     for i in y..y + h {
         for j in x..x + w {
-            if im[i * ww + j] != 0 {
+            if im[i * ww + j] & 1 != 0 {
                 return true;
             }
         }
     }
-    return false;
+    false
+}
+
+/// Endpoints of the fragments of the first chunk lying on the seam (or next
+/// to it), by their coordinate along the seam.
+struct SeamIndex {
+    sx: usize,
+    isv: bool,
+    // for the first and the last vertex of the fragments
+    ends: [HashMap<usize, Vec<usize>>; 2],
+}
+
+impl SeamIndex {
+    fn new(c0: &[VecDeque<[usize; 2]>], sx: usize, isv: bool) -> Self {
+        let mut index = SeamIndex {
+            sx,
+            isv,
+            ends: [HashMap::new(), HashMap::new()],
+        };
+        for (j, frag) in c0.iter().enumerate() {
+            index.insert(j, 0, frag[0]);
+            index.insert(j, 1, frag[frag.len() - 1]);
+        }
+        index
+    }
+
+    fn insert(&mut self, j: usize, end: usize, p: [usize; 2]) {
+        let (along, across) = if self.isv { (p[0], p[1]) } else { (p[1], p[0]) };
+        if across.abs_diff(self.sx) <= 1 {
+            self.ends[end].entry(along).or_default().push(j);
+        }
+    }
+
+    fn remove(&mut self, j: usize, end: usize, p: [usize; 2]) {
+        let along = if self.isv { p[0] } else { p[1] };
+        if let Some(frags) = self.ends[end].get_mut(&along) {
+            frags.retain(|&k| k != j);
+        }
+    }
+
+    /// The closest fragment with the given end less than 4 pixels from `along`,
+    /// the first one in case of a draw.
+    fn closest(&self, end: usize, along: usize) -> Option<usize> {
+        (along.saturating_sub(3)..=along + 3)
+            .filter_map(|a| Some((a.abs_diff(along), self.ends[end].get(&a)?)))
+            .flat_map(|(d, frags)| frags.iter().map(move |&j| (d, j)))
+            .min()
+            .map(|(_, j)| j)
+    }
 }
 
 /**merge ith fragment of second chunk to first chunk
  * @param c0   fragments from  first  chunk
  * @param c1i  ith fragment of second chunk
- * @param sx   (x or y) coordinate of the seam
- * @param isv  is vertical, not horizontal?
+ * @param index the endpoints of c0 on the seam
  * @param mode 2-bit flag,
  *             MSB = is matching the left (not right) end of the fragment from first  chunk
  *             LSB = is matching the right (not left) end of the fragment from second chunk
- * @return     matching successful?             
+ * @return     matching successful?
  */
 fn merge_impl(
-    c0: &mut Vec<Vec<[usize; 2]>>,
-    c1: &mut Vec<Vec<[usize; 2]>>,
-    i: usize,
-    sx: usize,
-    isv: bool,
+    c0: &mut [VecDeque<[usize; 2]>],
+    c1i: &mut Vec<[usize; 2]>,
+    index: &mut SeamIndex,
     mode: u8,
 ) -> bool {
     let b0: bool = (mode >> 1 & 1) > 0;
-    let b1: bool = (mode >> 0 & 1) > 0;
-    let mut mj: Option<usize> = None;
-    let mut md: i32 = 4;
-    let p1: [usize; 2] = c1[i][if b1 { 0 } else { c1[i].len() - 1 }];
-
-    if ((if isv { p1[1] } else { p1[0] }) as i32 - sx as i32).abs() > 0 {
+    let b1: bool = (mode & 1) > 0;
+    let p1: [usize; 2] = c1i[if b1 { 0 } else { c1i.len() - 1 }];
+    let (along, across) = if index.isv {
+        (p1[0], p1[1])
+    } else {
+        (p1[1], p1[0])
+    };
+    if across != index.sx {
         // not on the seam, skip
         return false;
     }
 
     // find the best match
-    for j in 0..c0.len() {
-        let p0: [usize; 2] = c0[j][if b0 { 0 } else { c0[j].len() - 1 }];
-        if ((if isv { p0[1] } else { p0[0] }) as i32 - sx as i32).abs() > 1 {
-            continue;
+    let end = if b0 { 0 } else { 1 };
+    let j = match index.closest(end, along) {
+        Some(j) => j,
+        None => return false,
+    };
+    let frag = &mut c0[j];
+    let old = if b0 { frag[0] } else { frag[frag.len() - 1] };
+    index.remove(j, end, old);
+    if b0 && b1 {
+        for &p in c1i.iter() {
+            frag.push_front(p);
         }
-        let d: i32 = ((if isv { p0[0] } else { p0[1] }) as i32
-            - (if isv { p1[0] } else { p1[1] }) as i32)
-            .abs();
-        if d < md {
-            mj = Some(j);
-            md = d;
+    } else if !b0 && b1 {
+        frag.extend(c1i.iter());
+    } else if b0 && !b1 {
+        for &p in c1i.iter().rev() {
+            frag.push_front(p);
         }
+    } else {
+        frag.extend(c1i.iter().rev());
     }
-
-    if mj.is_some() {
-        let j: usize = mj.unwrap();
-        if b0 && b1 {
-            c1[i].reverse();
-            c0[j].splice(0..0, c1[i].clone());
-        } else if !b0 && b1 {
-            c0[j].extend(c1[i].clone());
-        } else if b0 && !b1 {
-            c0[j].splice(0..0, c1[i].clone());
-        } else {
-            c1[i].reverse();
-            c0[j].extend(c1[i].clone());
-        }
-        c1[i].clear();
-        c1.remove(i);
-        return true;
-    }
-    return false;
+    let new = if b0 { frag[0] } else { frag[frag.len() - 1] };
+    index.insert(j, end, new);
+    c1i.clear();
+    true
 }
 
 /**merge fragments from two chunks
@@ -88,46 +145,30 @@ fn merge_impl(
  * @param dr   merge direction, HORIZONTAL or VERTICAL?
  */
 fn merge_frags(c0: &mut Vec<Vec<[usize; 2]>>, c1: &mut Vec<Vec<[usize; 2]>>, sx: usize, dr: u8) {
-    if c0.len() == 0 {
-        c0.extend(c1.clone());
-        c1.clear();
+    if c0.is_empty() {
+        c0.append(c1);
         return;
     }
-    if c1.len() == 0 {
+    if c1.is_empty() {
         return;
     }
-    for i in (0..c1.len()).rev() {
-        if dr == HORIZONTAL {
-            if merge_impl(c0, c1, i, sx, false, 1) {
-                continue;
-            };
-            if merge_impl(c0, c1, i, sx, false, 3) {
-                continue;
-            };
-            if merge_impl(c0, c1, i, sx, false, 0) {
-                continue;
-            };
-            if merge_impl(c0, c1, i, sx, false, 2) {
-                continue;
-            };
-        } else if dr == VERTICAL {
-            if merge_impl(c0, c1, i, sx, true, 1) {
-                continue;
-            };
-            if merge_impl(c0, c1, i, sx, true, 3) {
-                continue;
-            };
-            if merge_impl(c0, c1, i, sx, true, 0) {
-                continue;
-            };
-            if merge_impl(c0, c1, i, sx, true, 2) {
-                continue;
-            };
+    // the fragments on the seam are looked up by their position along it, and
+    // grow at both ends, so the merging is linear in their number and length
+    let isv = dr == VERTICAL;
+    let mut frags = mem::take(c0)
+        .into_iter()
+        .map(VecDeque::from)
+        .collect::<Vec<_>>();
+    let mut index = SeamIndex::new(&frags, sx, isv);
+    for c1i in c1.iter_mut().rev() {
+        for mode in [1, 3, 0, 2] {
+            if merge_impl(&mut frags, c1i, &mut index, mode) {
+                break;
+            }
         }
     }
-    c0.extend(c1.clone());
-    c1.clear();
-    return;
+    c0.extend(frags.into_iter().map(Vec::from));
+    c0.extend(c1.drain(..).filter(|c1i| !c1i.is_empty()));
 }
 
 /**recursive bottom: turn chunk into polyline fragments;
@@ -158,7 +199,7 @@ fn chunk_to_frags(
         let i: i32;
         let j: i32;
         if (k as i32) < (w as i32) {
-            i = y as i32 + 0;
+            i = y as i32;
             j = x as i32 + k as i32;
         } else if (k as i32) < (w as i32 + h as i32 - 1) {
             i = y as i32 + k as i32 - w as i32 + 1;
@@ -168,12 +209,12 @@ fn chunk_to_frags(
             j = x as i32 + w as i32 - (k as i32 - w as i32 - h as i32 + 3);
         } else {
             i = y as i32 + h as i32 - (k as i32 - w as i32 - h as i32 - w as i32 + 4);
-            j = x as i32 + 0;
+            j = x as i32;
         }
         let i: usize = i as usize;
         let j: usize = j as usize;
 
-        if im[i * ww + j] != 0 {
+        if im[i * ww + j] & 1 != 0 {
             // found an outgoing pixel
             if !on {
                 // left side of stroke
@@ -197,28 +238,26 @@ fn chunk_to_frags(
         frags.clear();
         frags.push(f);
     } else if frags.len() > 2 {
-        let mut ms: u8 = 0;
+        let mut ms: u32 = 0;
         let mut mi: i32 = -1;
         let mut mj: i32 = -1;
         // use convolution to find brightest blob
         for i in y + 1..y + h - 1 {
             for j in x + 1..x + w - 1 {
-                let s: u8 = (im[i * ww - ww + j - 1])
-                    + (im[i * ww - ww + j])
-                    + (im[i * ww - ww + j - 1 + 1])
-                    + (im[i * ww + j - 1])
-                    + (im[i * ww + j])
-                    + (im[i * ww + j + 1])
-                    + (im[i * ww + ww + j - 1])
-                    + (im[i * ww + ww + j])
-                    + (im[i * ww + ww + j + 1]);
-                if s > ms {
-                    mi = i as i32;
-                    mj = j as i32;
-                    ms = s;
-                } else if s == ms
-                    && (j as i32 - (x + w / 2) as i32).abs() + (i as i32 - (y + h / 2) as i32).abs()
-                        < (mj - (x + w / 2) as i32).abs() + (mi - (y + h / 2) as i32).abs()
+                let s: u32 = u32::from(im[i * ww - ww + j - 1] & 1)
+                    + u32::from(im[i * ww - ww + j] & 1)
+                    + u32::from(im[i * ww - ww + j - 1 + 1] & 1)
+                    + u32::from(im[i * ww + j - 1] & 1)
+                    + u32::from(im[i * ww + j] & 1)
+                    + u32::from(im[i * ww + j + 1] & 1)
+                    + u32::from(im[i * ww + ww + j - 1] & 1)
+                    + u32::from(im[i * ww + ww + j] & 1)
+                    + u32::from(im[i * ww + ww + j + 1] & 1);
+                if s > ms
+                    || (s == ms
+                        && (j as i32 - (x + w / 2) as i32).abs()
+                            + (i as i32 - (y + h / 2) as i32).abs()
+                            < (mj - (x + w / 2) as i32).abs() + (mi - (y + h / 2) as i32).abs())
                 {
                     mi = i as i32;
                     mj = j as i32;
@@ -227,30 +266,23 @@ fn chunk_to_frags(
             }
         }
         if mi != -1 {
-            for i in 0..frags.len() {
-                frags[i][1][0] = mj as usize;
-                frags[i][1][1] = mi as usize;
+            for frag in frags.iter_mut() {
+                frag[1] = [mj as usize, mi as usize];
             }
         }
     }
-    return frags;
+    frags
 }
 
-/**Trace skeleton from thinning result.
- * Algorithm:
- * 1. if chunk size is small enough, reach recursive bottom and turn it into segments
- * 2. attempt to split the chunk into 2 smaller chunks, either horizontall or vertically;
- *    find the best "seam" to carve along, and avoid possible degenerate cases
- * 3. recurse on each chunk, and merge their segments
+/**find the best seam to split a chunk along, either horizontally or vertically;
+ * it must not cross the skeleton on the borders of the chunk, and should cross
+ * as few skeleton pixels as possible, near the middle of the chunk
  *
- * @param x       left of   chunk
- * @param y       top of    chunk
- * @param w       width of  chunk
- * @param h       height of chunk
- * @param iter    current iteration
- * @return        an array of polylines
-*/
-pub fn trace_skeleton(
+ * @return the two halves, the (x or y) coordinate of the seam and the merge
+ *         direction, or None if the chunk can't be split
+ */
+#[allow(clippy::too_many_arguments)]
+fn find_seam(
     im: &[u8],
     ww: usize,
     hh: usize,
@@ -259,67 +291,52 @@ pub fn trace_skeleton(
     w: usize,
     h: usize,
     chunk_size: usize,
-    max_iter: usize,
-) -> Vec<Vec<[usize; 2]>> {
-    // for i in y..y+h{for j in x..x+w{print!("{}",im[i*ww+j]);}println!();}
-
-    if max_iter <= 0 {
-        dbg!("no");
-        return vec![];
-    }
-    if w <= chunk_size && h <= chunk_size {
-        return chunk_to_frags(im, ww, hh, x, y, w, h);
-    }
+) -> Option<([usize; 4], [usize; 4], usize, u8)> {
     let mut ms: usize = ww + hh;
     let mut mi: i32 = -1;
     let mut mj: i32 = -1;
     if h > chunk_size {
-        for i in y + 3..y + h - 3 {
-            if im[i * ww + x] > 0
-                || im[(i - 1) * ww + x] > 0
-                || im[i * ww + x + w - 1] > 0
-                || im[(i - 1) * ww + x + w - 1] > 0
+        for i in y + 3..(y + h).saturating_sub(3) {
+            if im[i * ww + x] & 1 != 0
+                || im[(i - 1) * ww + x] & 1 != 0
+                || im[i * ww + x + w - 1] & 1 != 0
+                || im[(i - 1) * ww + x + w - 1] & 1 != 0
             {
                 continue;
             }
             let mut s: usize = 0;
             for j in x..x + w {
-                s += im[i * ww + j] as usize;
-                s += im[(i - 1) * ww + j] as usize;
+                s += (im[i * ww + j] & 1) as usize;
+                s += (im[(i - 1) * ww + j] & 1) as usize;
             }
-            if s < ms {
-                ms = s;
-                mi = i as i32;
-            } else if s == ms
-                && (i as i32 - (y + h / 2) as i32).abs() < (mi as i32 - (y + h / 2) as i32).abs()
+            // if there is a draw (very common), we want the seam to be near the middle
+            // to balance the divide and conquer tree
+            if s < ms
+                || (s == ms
+                    && (i as i32 - (y + h / 2) as i32).abs() < (mi - (y + h / 2) as i32).abs())
             {
-                // if there is a draw (very common), we want the seam to be near the middle
-                // to balance the divide and conquer tree
                 ms = s;
                 mi = i as i32;
             }
         }
     }
     if w > chunk_size {
-        for j in x + 3..x + w - 3 {
-            if im[ww * y + j] > 0
-                || im[ww * (y + h) - ww + j] > 0
-                || im[ww * y + j - 1] > 0
-                || im[ww * (y + h) - ww + j - 1] > 0
+        for j in x + 3..(x + w).saturating_sub(3) {
+            if im[ww * y + j] & 1 != 0
+                || im[ww * (y + h) - ww + j] & 1 != 0
+                || im[ww * y + j - 1] & 1 != 0
+                || im[ww * (y + h) - ww + j - 1] & 1 != 0
             {
                 continue;
             }
             let mut s: usize = 0;
             for i in y..y + h {
-                s += im[i * ww + j] as usize;
-                s += im[i * ww + j - 1] as usize;
+                s += (im[i * ww + j] & 1) as usize;
+                s += (im[i * ww + j - 1] & 1) as usize;
             }
-            if s < ms {
-                ms = s;
-                mi = -1;
-                mj = j as i32;
-            } else if s == ms
-                && (j as i32 - (x + w / 2) as i32).abs() < (mj as i32 - (x + w / 2) as i32).abs()
+            if s < ms
+                || (s == ms
+                    && (j as i32 - (x + w / 2) as i32).abs() < (mj - (x + w / 2) as i32).abs())
             {
                 ms = s;
                 mi = -1;
@@ -327,58 +344,396 @@ pub fn trace_skeleton(
             }
         }
     }
-    let (mut l0, mut l1, mut l2, mut l3): (usize, usize, usize, usize) =
-        (usize::max_value(), 0, 0, 0);
-    let (mut r0, mut r1, mut r2, mut r3): (usize, usize, usize, usize) =
-        (usize::max_value(), 0, 0, 0);
-    let mut dr: u8 = 0;
-    let mut sx: usize = 0;
 
     if h > chunk_size && mi != -1 {
-        l0 = x;
-        l1 = y;
-        l2 = w;
-        l3 = (mi as i32 - y as i32) as usize;
-        r0 = x;
-        r1 = mi as usize;
-        r2 = w;
-        r3 = (y as i32 + h as i32 - mi as i32) as usize;
-        dr = VERTICAL;
-        sx = mi as usize;
+        let mi = mi as usize;
+        Some(([x, y, w, mi - y], [x, mi, w, y + h - mi], mi, VERTICAL))
     } else if w > chunk_size && mj != -1 {
-        l0 = x;
-        l1 = y;
-        l2 = (mj as i32 - x as i32) as usize;
-        l3 = h;
-        r0 = mj as usize;
-        r1 = y;
-        r2 = (x as i32 + w as i32 - mj as i32) as usize;
-        r3 = h;
-        dr = HORIZONTAL;
-        sx = mj as usize;
+        let mj = mj as usize;
+        Some(([x, y, mj - x, h], [mj, y, x + w - mj, h], mj, HORIZONTAL))
+    } else {
+        None
     }
-    let mut frags: Vec<Vec<[usize; 2]>> = vec![];
-    if l0 != usize::max_value() && not_empty(im, ww, hh, l0, l1, l2, l3) {
-        // if there are no white pixels, don't waste time
-        merge_frags(
-            &mut frags,
-            &mut trace_skeleton(im, ww, hh, l0, l1, l2, l3, chunk_size, max_iter - 1),
-            sx,
-            dr,
-        );
+}
+
+enum Task {
+    /// trace a chunk, given as x, y, w, h, with the remaining depth
+    Trace([usize; 4], usize),
+    /// merge the fragments of the two halves of a split chunk, on top of the
+    /// stack if they weren't empty
+    Merge {
+        left: bool,
+        right: bool,
+        sx: usize,
+        dr: u8,
+    },
+}
+
+/**Trace skeleton from thinning result.
+ * Algorithm:
+ * 1. if chunk size is small enough, reach recursive bottom and turn it into segments
+ * 2. attempt to split the chunk into 2 smaller chunks, either horizontall or vertically;
+ *    find the best "seam" to carve along, and avoid possible degenerate cases
+ * 3. recurse on each chunk, and merge their segments
+ *
+ * The recursion is unrolled into an explicit stack of tasks, so deep trees
+ * can't overflow the call stack. The chunks more than `max_iter` levels deep
+ * are skipped with a warning. Only the lowest bit of the pixels is read,
+ * like in the thinning.
+ *
+ * @param x       left of   chunk
+ * @param y       top of    chunk
+ * @param w       width of  chunk
+ * @param h       height of chunk
+ * @param max_iter maximum depth of the divide and conquer tree
+ * @return        an array of polylines
+*/
+#[allow(clippy::too_many_arguments)]
+pub fn trace_skeleton(
+    im: &[u8],
+    ww: usize,
+    hh: usize,
+    x: usize,
+    y: usize,
+    w: usize,
+    h: usize,
+    chunk_size: usize,
+    max_iter: usize,
+) -> Vec<Vec<[usize; 2]>> {
+    let mut tasks = vec![Task::Trace([x, y, w, h], max_iter)];
+    let mut results: Vec<Vec<Vec<[usize; 2]>>> = Vec::new();
+    let mut skipped = 0;
+    while let Some(task) = tasks.pop() {
+        match task {
+            Task::Trace([x, y, w, h], depth) => {
+                if depth == 0 {
+                    log::warn!(
+                        "Reached the maximum tracing depth of {max_iter}, skipping the {w}x{h} chunk at {x}, {y}"
+                    );
+                    skipped += 1;
+                    results.push(vec![]);
+                    continue;
+                }
+                if w <= chunk_size && h <= chunk_size {
+                    results.push(chunk_to_frags(im, ww, hh, x, y, w, h));
+                    continue;
+                }
+                match find_seam(im, ww, hh, x, y, w, h, chunk_size) {
+                    Some((l, r, sx, dr)) => {
+                        // if there are no white pixels, don't waste time
+                        let left = not_empty(im, ww, hh, l[0], l[1], l[2], l[3]);
+                        let right = not_empty(im, ww, hh, r[0], r[1], r[2], r[3]);
+                        tasks.push(Task::Merge {
+                            left,
+                            right,
+                            sx,
+                            dr,
+                        });
+                        // the left half is traced first, so its fragments end up below
+                        if right {
+                            tasks.push(Task::Trace(r, depth - 1));
+                        }
+                        if left {
+                            tasks.push(Task::Trace(l, depth - 1));
+                        }
+                    }
+                    // splitting failed! do the recursive bottom instead
+                    None => results.push(chunk_to_frags(im, ww, hh, x, y, w, h)),
+                }
+            }
+            Task::Merge {
+                left,
+                right,
+                sx,
+                dr,
+            } => {
+                let mut r = if right {
+                    results.pop().unwrap()
+                } else {
+                    vec![]
+                };
+                let mut l = if left { results.pop().unwrap() } else { vec![] };
+                let mut frags = vec![];
+                merge_frags(&mut frags, &mut l, sx, dr);
+                merge_frags(&mut frags, &mut r, sx, dr);
+                results.push(frags);
+            }
+        }
     }
-    if r0 != usize::max_value() && not_empty(im, ww, hh, r0, r1, r2, r3) {
-        // if there are no white pixels, don't waste time
-        merge_frags(
-            &mut frags,
-            &mut trace_skeleton(im, ww, hh, r0, r1, r2, r3, chunk_size, max_iter - 1),
-            sx,
-            dr,
+    if skipped > 0 {
+        log::warn!(
+            "Skipped {skipped} chunks, some polylines are missing; try a larger maximum depth"
         );
     }
-    if mi == -1 && mj == -1 {
-        // splitting failed! do the recursive bottom instead
-        return chunk_to_frags(im, ww, hh, x, y, w, h);
+    results.pop().unwrap()
+}
+
+/**Same as `trace_skeleton`, but tracing the two halves of the chunks larger
+ * than `PARALLEL_AREA` pixels in parallel. The halves are merged in the same
+ * order, so the output doesn't depend on the scheduling.
+ */
+#[allow(clippy::too_many_arguments)]
+pub fn trace_skeleton_par(
+    im: &[u8],
+    ww: usize,
+    hh: usize,
+    x: usize,
+    y: usize,
+    w: usize,
+    h: usize,
+    chunk_size: usize,
+    max_iter: usize,
+) -> Vec<Vec<[usize; 2]>> {
+    if max_iter == 0 || w * h < PARALLEL_AREA || (w <= chunk_size && h <= chunk_size) {
+        return trace_skeleton(im, ww, hh, x, y, w, h, chunk_size, max_iter);
+    }
+    let (l, r, sx, dr) = match find_seam(im, ww, hh, x, y, w, h, chunk_size) {
+        Some(seam) => seam,
+        None => return chunk_to_frags(im, ww, hh, x, y, w, h),
+    };
+    let trace_half = |c: [usize; 4]| {
+        if not_empty(im, ww, hh, c[0], c[1], c[2], c[3]) {
+            trace_skeleton_par(im, ww, hh, c[0], c[1], c[2], c[3], chunk_size, max_iter - 1)
+        } else {
+            vec![]
+        }
+    };
+    let (mut left, mut right) = rayon::join(|| trace_half(l), || trace_half(r));
+    let mut frags = vec![];
+    merge_frags(&mut frags, &mut left, sx, dr);
+    merge_frags(&mut frags, &mut right, sx, dr);
+    frags
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_util::{random_mask, Rng},
+        thinning_zs,
+    };
+
+    /// The recursive tracer from before the explicit stack, with the linear
+    /// merge, to check that the faster ones give the same polylines.
+    mod recursive {
+        use super::super::{chunk_to_frags, not_empty, HORIZONTAL, VERTICAL};
+
+        fn merge_impl(
+            c0: &mut [Vec<[usize; 2]>],
+            c1: &mut Vec<Vec<[usize; 2]>>,
+            i: usize,
+            sx: usize,
+            isv: bool,
+            mode: u8,
+        ) -> bool {
+            let b0 = (mode >> 1 & 1) > 0;
+            let b1 = (mode & 1) > 0;
+            let mut mj = None;
+            let mut md = 4;
+            let p1 = c1[i][if b1 { 0 } else { c1[i].len() - 1 }];
+            if (if isv { p1[1] } else { p1[0] }) != sx {
+                return false;
+            }
+            for (j, c0j) in c0.iter().enumerate() {
+                let p0 = c0j[if b0 { 0 } else { c0j.len() - 1 }];
+                if ((if isv { p0[1] } else { p0[0] }) as i32 - sx as i32).abs() > 1 {
+                    continue;
+                }
+                let d = ((if isv { p0[0] } else { p0[1] }) as i32
+                    - (if isv { p1[0] } else { p1[1] }) as i32)
+                    .abs();
+                if d < md {
+                    mj = Some(j);
+                    md = d;
+                }
+            }
+            let Some(j) = mj else {
+                return false;
+            };
+            let mut c1i = c1.remove(i);
+            if b0 == b1 {
+                c1i.reverse();
+            }
+            if b0 {
+                c0[j].splice(0..0, c1i);
+            } else {
+                c0[j].extend(c1i);
+            }
+            true
+        }
+
+        fn merge_frags(
+            c0: &mut Vec<Vec<[usize; 2]>>,
+            c1: &mut Vec<Vec<[usize; 2]>>,
+            sx: usize,
+            dr: u8,
+        ) {
+            if c0.is_empty() {
+                c0.append(c1);
+                return;
+            }
+            for i in (0..c1.len()).rev() {
+                for mode in [1, 3, 0, 2] {
+                    if merge_impl(c0, c1, i, sx, dr == VERTICAL, mode) {
+                        break;
+                    }
+                }
+            }
+            c0.append(c1);
+        }
+
+        #[allow(clippy::too_many_arguments)]
+        pub fn trace_skeleton(
+            im: &[u8],
+            ww: usize,
+            hh: usize,
+            x: usize,
+            y: usize,
+            w: usize,
+            h: usize,
+            chunk_size: usize,
+            max_iter: usize,
+        ) -> Vec<Vec<[usize; 2]>> {
+            if max_iter == 0 {
+                return vec![];
+            }
+            if w <= chunk_size && h <= chunk_size {
+                return chunk_to_frags(im, ww, hh, x, y, w, h);
+            }
+            let mut ms = ww + hh;
+            let mut mi: i32 = -1;
+            let mut mj: i32 = -1;
+            if h > chunk_size {
+                for i in y + 3..y + h - 3 {
+                    if im[i * ww + x] > 0
+                        || im[(i - 1) * ww + x] > 0
+                        || im[i * ww + x + w - 1] > 0
+                        || im[(i - 1) * ww + x + w - 1] > 0
+                    {
+                        continue;
+                    }
+                    let s = (x..x + w)
+                        .map(|j| (im[i * ww + j] + im[(i - 1) * ww + j]) as usize)
+                        .sum();
+                    if s < ms
+                        || s == ms
+                            && (i as i32 - (y + h / 2) as i32).abs()
+                                < (mi - (y + h / 2) as i32).abs()
+                    {
+                        ms = s;
+                        mi = i as i32;
+                    }
+                }
+            }
+            if w > chunk_size {
+                for j in x + 3..x + w - 3 {
+                    if im[ww * y + j] > 0
+                        || im[ww * (y + h) - ww + j] > 0
+                        || im[ww * y + j - 1] > 0
+                        || im[ww * (y + h) - ww + j - 1] > 0
+                    {
+                        continue;
+                    }
+                    let s = (y..y + h)
+                        .map(|i| (im[i * ww + j] + im[i * ww + j - 1]) as usize)
+                        .sum();
+                    if s < ms
+                        || s == ms
+                            && (j as i32 - (x + w / 2) as i32).abs()
+                                < (mj - (x + w / 2) as i32).abs()
+                    {
+                        ms = s;
+                        mi = -1;
+                        mj = j as i32;
+                    }
+                }
+            }
+            let (l, r, sx, dr) = if h > chunk_size && mi != -1 {
+                let mi = mi as usize;
+                ([x, y, w, mi - y], [x, mi, w, y + h - mi], mi, VERTICAL)
+            } else if w > chunk_size && mj != -1 {
+                let mj = mj as usize;
+                ([x, y, mj - x, h], [mj, y, x + w - mj, h], mj, HORIZONTAL)
+            } else {
+                return chunk_to_frags(im, ww, hh, x, y, w, h);
+            };
+            let mut frags = vec![];
+            for c in [l, r] {
+                if not_empty(im, ww, hh, c[0], c[1], c[2], c[3]) {
+                    let mut half = trace_skeleton(
+                        im,
+                        ww,
+                        hh,
+                        c[0],
+                        c[1],
+                        c[2],
+                        c[3],
+                        chunk_size,
+                        max_iter - 1,
+                    );
+                    merge_frags(&mut frags, &mut half, sx, dr);
+                }
+            }
+            frags
+        }
+    }
+
+    fn thinned_mask(rng: &mut Rng, w: usize, h: usize) -> Vec<u8> {
+        let mut im = random_mask(rng, w, h);
+        thinning_zs(&mut im, w, h);
+        im
+    }
+
+    #[test]
+    fn matches_recursive_tracer() {
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+        for _ in 0..200 {
+            let (w, h) = (1 + rng.below(120), 1 + rng.below(120));
+            let chunk_size = 4 + rng.below(12);
+            let im = thinned_mask(&mut rng, w, h);
+            let expected = recursive::trace_skeleton(&im, w, h, 0, 0, w, h, chunk_size, 999);
+            let actual = trace_skeleton(&im, w, h, 0, 0, w, h, chunk_size, 999);
+            assert_eq!(actual, expected, "{w}x{h}, chunks of {chunk_size}");
+        }
+    }
+
+    #[test]
+    fn parallel_matches_recursive_tracer() {
+        let mut rng = Rng(0xd1b5_4a32_d192_ed03);
+        for threads in [1, 4] {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap();
+            for _ in 0..4 {
+                // large enough to be split in parallel
+                let (w, h) = (300 + rng.below(200), 300 + rng.below(200));
+                let im = thinned_mask(&mut rng, w, h);
+                let expected = recursive::trace_skeleton(&im, w, h, 0, 0, w, h, 10, 999);
+                let actual = pool.install(|| trace_skeleton_par(&im, w, h, 0, 0, w, h, 10, 999));
+                assert_eq!(actual, expected, "{w}x{h} on {threads} threads");
+            }
+        }
+    }
+
+    #[test]
+    fn traces_0_255_masks() {
+        let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+        for _ in 0..100 {
+            let (w, h) = (1 + rng.below(60), 1 + rng.below(60));
+            let im = thinned_mask(&mut rng, w, h);
+            let expected = trace_skeleton(&im, w, h, 0, 0, w, h, 10, 999);
+            let im = im.iter().map(|&p| p * 255).collect::<Vec<_>>();
+            assert_eq!(
+                trace_skeleton(&im, w, h, 0, 0, w, h, 10, 999),
+                expected,
+                "{w}x{h}"
+            );
+            assert_eq!(
+                trace_skeleton_par(&im, w, h, 0, 0, w, h, 10, 999),
+                expected,
+                "{w}x{h}"
+            );
+        }
     }
-    return frags;
 }
@@ -0,0 +1,32 @@
+//================================
+// TEST HELPERS
+//================================
+
+/// xorshift64, enough to make up the test images without a dependency.
+pub struct Rng(pub u64);
+
+impl Rng {
+    pub fn below(&mut self, n: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % n as u64) as usize
+    }
+}
+
+/// A mask with some filled rectangles, thick enough to need a few
+/// iterations, over noise of random density.
+pub fn random_mask(rng: &mut Rng, w: usize, h: usize) -> Vec<u8> {
+    let density = rng.below(6);
+    let mut im = (0..w * h)
+        .map(|_| (rng.below(10) < density) as u8)
+        .collect::<Vec<_>>();
+    for _ in 0..rng.below(6) {
+        let (x, y) = (rng.below(w), rng.below(h));
+        let (rw, rh) = (1 + rng.below(w - x), 1 + rng.below(h - y));
+        for i in y..y + rh {
+            im[i * w + x..i * w + x + rw].fill(1);
+        }
+    }
+    im
+}
@@ -0,0 +1,109 @@
+/// Iterates over the `(win_x, win_y, win_w, win_h)` windows of a tiled image,
+/// row by row. The tiles on the right and bottom edges are clipped to the image.
+pub fn windows(
+    width: usize,
+    height: usize,
+    tile_width: usize,
+    tile_height: usize,
+) -> impl Iterator<Item = (usize, usize, usize, usize)> {
+    let ntx = width.div_ceil(tile_width);
+    let nty = height.div_ceil(tile_height);
+    (0..nty).flat_map(move |ti_y| {
+        (0..ntx).map(move |ti_x| {
+            let win_x = ti_x * tile_width;
+            let win_y = ti_y * tile_height;
+            let win_w = tile_width.min(width - win_x);
+            let win_h = tile_height.min(height - win_y);
+            (win_x, win_y, win_w, win_h)
+        })
+    })
+}
+
+/// A rectangle of pixels, `x0..x1` by `y0..y1`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rect {
+    pub x0: usize,
+    pub y0: usize,
+    pub x1: usize,
+    pub y1: usize,
+}
+
+impl Rect {
+    pub const EMPTY: Rect = Rect {
+        x0: usize::MAX,
+        y0: usize::MAX,
+        x1: 0,
+        y1: 0,
+    };
+
+    pub fn new(x: usize, y: usize, w: usize, h: usize) -> Self {
+        Self {
+            x0: x,
+            y0: y,
+            x1: x + w,
+            y1: y + h,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.x0 >= self.x1 || self.y0 >= self.y1
+    }
+
+    pub fn width(&self) -> usize {
+        self.x1.saturating_sub(self.x0)
+    }
+
+    pub fn height(&self) -> usize {
+        self.y1.saturating_sub(self.y0)
+    }
+
+    /// Grows the rectangle to include the pixel `(x, y)`.
+    pub fn add(&mut self, x: usize, y: usize) {
+        self.x0 = self.x0.min(x);
+        self.y0 = self.y0.min(y);
+        self.x1 = self.x1.max(x + 1);
+        self.y1 = self.y1.max(y + 1);
+    }
+
+    pub fn union(&self, other: &Rect) -> Rect {
+        if self.is_empty() {
+            return *other;
+        }
+        if other.is_empty() {
+            return *self;
+        }
+        Rect {
+            x0: self.x0.min(other.x0),
+            y0: self.y0.min(other.y0),
+            x1: self.x1.max(other.x1),
+            y1: self.y1.max(other.y1),
+        }
+    }
+
+    pub fn intersect(&self, other: &Rect) -> Rect {
+        let r = Rect {
+            x0: self.x0.max(other.x0),
+            y0: self.y0.max(other.y0),
+            x1: self.x1.min(other.x1),
+            y1: self.y1.min(other.y1),
+        };
+        if r.is_empty() {
+            Rect::EMPTY
+        } else {
+            r
+        }
+    }
+
+    /// Grows the rectangle by `n` pixels on each side, clipped to a `w` by `h` image.
+    pub fn expand(&self, n: usize, w: usize, h: usize) -> Rect {
+        if self.is_empty() {
+            return *self;
+        }
+        Rect {
+            x0: self.x0.saturating_sub(n),
+            y0: self.y0.saturating_sub(n),
+            x1: (self.x1 + n).min(w),
+            y1: (self.y1 + n).min(h),
+        }
+    }
+}
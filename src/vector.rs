@@ -0,0 +1,141 @@
+use std::{error::Error, path::Path};
+
+use gdal::{
+    vector::{FieldValue, Geometry, LayerAccess, LayerOptions, OGRFieldType, OGRwkbGeometryType},
+    Driver,
+};
+
+use crate::{
+    classify::{self, Node},
+    geo::Georef,
+};
+
+/// Picks an OGR driver from the extension of `path`.
+pub fn driver_for(path: &str) -> Result<Driver, Box<dyn Error>> {
+    let extension = Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    let name = match extension.as_str() {
+        "gpkg" => "GPKG",
+        "geojson" | "json" => "GeoJSON",
+        "shp" => "ESRI Shapefile",
+        "fgb" => "FlatGeobuf",
+        "csv" => "CSV",
+        _ => return Err(format!("don't know which driver to use for {path}").into()),
+    };
+    Ok(Driver::get_by_name(name)?)
+}
+
+/// Writes the traced polylines as a `LineString` layer, with the vertices
+/// transformed by `georef`. Each polyline has a `ring` attribute telling whether
+/// it's a closed loop; with `ring_polygons`, these go into a separate `Polygon`
+/// layer instead. When `components` is given, it holds the component id of
+/// each polyline.
+pub fn write_polylines(
+    path: &str,
+    polylines: &[Vec<[f64; 2]>],
+    rings: &[bool],
+    ring_polygons: bool,
+    components: Option<&[u32]>,
+    georef: &Georef,
+) -> Result<(), Box<dyn Error>> {
+    let mut ds = driver_for(path)?.create_vector_only(path)?;
+    let mut fields = vec![("ring", OGRFieldType::OFTInteger)];
+    if components.is_some() {
+        fields.push(("component", OGRFieldType::OFTInteger64));
+    }
+    let names = fields.iter().map(|(name, _)| *name).collect::<Vec<_>>();
+    let values = |i: usize| {
+        let mut values = vec![FieldValue::IntegerValue(rings[i] as i32)];
+        if let Some(components) = components {
+            values.push(FieldValue::Integer64Value(components[i] as i64));
+        }
+        values
+    };
+
+    let mut layer = ds.create_layer(LayerOptions {
+        name: "skeleton",
+        srs: georef.srs(),
+        ty: OGRwkbGeometryType::wkbLineString,
+        ..Default::default()
+    })?;
+    layer.create_defn_fields(&fields)?;
+    for (i, polyline) in polylines.iter().enumerate() {
+        if ring_polygons && rings[i] {
+            continue;
+        }
+        let geom = line_string(polyline, georef, OGRwkbGeometryType::wkbLineString)?;
+        layer.create_feature_fields(geom, &names, &values(i))?;
+    }
+
+    if ring_polygons {
+        let mut layer = ds.create_layer(LayerOptions {
+            name: "rings",
+            srs: georef.srs(),
+            ty: OGRwkbGeometryType::wkbPolygon,
+            ..Default::default()
+        })?;
+        layer.create_defn_fields(&fields)?;
+        for (i, polyline) in polylines.iter().enumerate() {
+            if !rings[i] {
+                continue;
+            }
+            let ring = line_string(polyline, georef, OGRwkbGeometryType::wkbLinearRing)?;
+            let mut geom = Geometry::empty(OGRwkbGeometryType::wkbPolygon)?;
+            geom.add_geometry(ring)?;
+            layer.create_feature_fields(geom, &names, &values(i))?;
+        }
+    }
+
+    Ok(())
+}
+
+fn line_string(
+    polyline: &[[f64; 2]],
+    georef: &Georef,
+    ty: OGRwkbGeometryType::Type,
+) -> Result<Geometry, Box<dyn Error>> {
+    let mut xs = polyline.iter().map(|p| p[0]).collect::<Vec<_>>();
+    let mut ys = polyline.iter().map(|p| p[1]).collect::<Vec<_>>();
+    georef.transform(&mut xs, &mut ys)?;
+    let mut geom = Geometry::empty(ty)?;
+    for (&x, &y) in xs.iter().zip(&ys) {
+        geom.add_point_2d((x, y));
+    }
+    Ok(geom)
+}
+
+/// Writes the endpoints and junctions as a `Point` layer, with their class
+/// and crossing number.
+pub fn write_nodes(path: &str, nodes: &[Node], georef: &Georef) -> Result<(), Box<dyn Error>> {
+    let mut ds = driver_for(path)?.create_vector_only(path)?;
+    let mut layer = ds.create_layer(LayerOptions {
+        name: "nodes",
+        srs: georef.srs(),
+        ty: OGRwkbGeometryType::wkbPoint,
+        ..Default::default()
+    })?;
+    layer.create_defn_fields(&[
+        ("class", OGRFieldType::OFTString),
+        ("crossing_number", OGRFieldType::OFTInteger),
+    ])?;
+
+    for node in nodes {
+        let (mut x, mut y) = ([node.x as f64], [node.y as f64]);
+        georef.transform(&mut x, &mut y)?;
+        let mut geom = Geometry::empty(OGRwkbGeometryType::wkbPoint)?;
+        geom.add_point_2d((x[0], y[0]));
+        layer.create_feature_fields(
+            geom,
+            &["class", "crossing_number"],
+            &[
+                FieldValue::StringValue(classify::class_name(node.class).to_string()),
+                FieldValue::IntegerValue(node.crossing_number as i32),
+            ],
+        )?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,208 @@
+use std::collections::HashSet;
+
+use indicatif::ProgressBar;
+
+//================================
+// EXACT TRACING
+//================================
+// Instead of fitting straight segments to chunks, the skeleton is followed one
+// pixel at a time. Two skeleton pixels are linked if they're 4-neighbours, or
+// diagonal neighbours without a skeleton pixel at one of the two corners
+// between them, so that staircases don't look like junctions. The pixels with
+// two links are on a line, the others are endpoints or junctions. Each line
+// between these nodes, or loop without any, becomes a polyline going through
+// the centre of every pixel on it. Pixels outside the chunk are background.
+
+/// The chunk `[x0, y0, x1, y1)` being traced.
+struct Chunk<'a> {
+    im: &'a [u8],
+    width: usize,
+    rect: [usize; 4],
+}
+
+impl Chunk<'_> {
+    fn is_set(&self, x: isize, y: isize) -> bool {
+        let [x0, y0, x1, y1] = self.rect;
+        x >= x0 as isize
+            && y >= y0 as isize
+            && x < x1 as isize
+            && y < y1 as isize
+            && self.im[y as usize * self.width + x as usize] & 1 != 0
+    }
+
+    /// The pixels linked to `p`, and how many there are.
+    fn links(&self, p: [usize; 2]) -> ([[usize; 2]; 8], usize) {
+        let (x, y) = (p[0] as isize, p[1] as isize);
+        let mut links = [[0; 2]; 8];
+        let mut n = 0;
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                if (dx, dy) == (0, 0) || !self.is_set(x + dx, y + dy) {
+                    continue;
+                }
+                if dx != 0 && dy != 0 && (self.is_set(x + dx, y) || self.is_set(x, y + dy)) {
+                    continue;
+                }
+                links[n] = [(x + dx) as usize, (y + dy) as usize];
+                n += 1;
+            }
+        }
+        (links, n)
+    }
+
+    /// Follows the line from `prev` to `p`, adding its pixels to `path` up to
+    /// the first node. Returns true, without adding it, when getting back to
+    /// `origin` instead.
+    fn follow(
+        &self,
+        mut prev: [usize; 2],
+        mut p: [usize; 2],
+        origin: [usize; 2],
+        path: &mut Vec<[usize; 2]>,
+        seen: &mut HashSet<[usize; 2]>,
+    ) -> bool {
+        loop {
+            if p == origin {
+                return true;
+            }
+            let (links, n) = self.links(p);
+            path.push(p);
+            if n != 2 {
+                return false;
+            }
+            seen.insert(p);
+            let next = if links[0] == prev { links[1] } else { links[0] };
+            prev = p;
+            p = next;
+        }
+    }
+}
+
+/// Traces the skeleton pixels of the `w` by `h` chunk at `x`, `y` of the
+/// `ww` by `hh` image `im` into polylines, one for each line between two
+/// endpoints or junctions. Loops without any start and end at the same pixel.
+pub fn trace_pixels(
+    im: &[u8],
+    ww: usize,
+    _hh: usize,
+    x: usize,
+    y: usize,
+    w: usize,
+    h: usize,
+) -> Vec<Vec<[usize; 2]>> {
+    let chunk = Chunk {
+        im,
+        width: ww,
+        rect: [x, y, x + w, y + h],
+    };
+    let mut polylines = Vec::new();
+    // the line pixels already traced
+    let mut seen = HashSet::new();
+
+    log::info!("Starting exact tracing");
+    let pb = ProgressBar::new(h as u64).with_message("Starting exact tracing");
+    for i in y..y + h {
+        for j in x..x + w {
+            let p = [j, i];
+            if !chunk.is_set(j as isize, i as isize) {
+                continue;
+            }
+            let (links, n) = chunk.links(p);
+            if n != 2 {
+                // the lines with pixels of their own are traced from those
+                for &q in &links[..n] {
+                    if chunk.links(q).1 != 2 && (q[1], q[0]) > (i, j) {
+                        polylines.push(vec![p, q]);
+                    }
+                }
+                continue;
+            }
+            if seen.contains(&p) {
+                continue;
+            }
+
+            // walk both ways from `p`, unless the first one goes around a loop
+            seen.insert(p);
+            let mut forward = vec![p];
+            if chunk.follow(p, links[1], p, &mut forward, &mut seen) {
+                forward.push(p);
+                polylines.push(forward);
+                continue;
+            }
+            let mut backward = vec![];
+            chunk.follow(p, links[0], p, &mut backward, &mut seen);
+            backward.reverse();
+            backward.extend(forward);
+            polylines.push(backward);
+        }
+        pb.inc(1);
+    }
+    pb.finish();
+
+    log::info!("Traced {} polylines", polylines.len());
+    polylines
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::{
+        test_util::{random_mask, Rng},
+        thinning_zs,
+    };
+
+    #[test]
+    fn visits_every_pixel_once() {
+        let mut rng = Rng(0xd1b5_4a32_d192_ed03);
+        for case in 0..300 {
+            let (ww, hh) = (1 + rng.below(30), 1 + rng.below(30));
+            let mut im = random_mask(&mut rng, ww, hh);
+            if case % 2 == 0 {
+                thinning_zs(&mut im, ww, hh);
+            }
+            let (x, y) = (rng.below(ww), rng.below(hh));
+            let (w, h) = (1 + rng.below(ww - x), 1 + rng.below(hh - y));
+            let chunk = Chunk {
+                im: &im,
+                width: ww,
+                rect: [x, y, x + w, y + h],
+            };
+
+            let polylines = trace_pixels(&im, ww, hh, x, y, w, h);
+            let mut visits = HashMap::<[usize; 2], usize>::new();
+            for polyline in &polylines {
+                for pair in polyline.windows(2) {
+                    assert!(chunk.links(pair[0]).0.contains(&pair[1]), "{pair:?}");
+                }
+                // a loop without nodes starts and ends at the same line pixel
+                let closed = polyline.len() > 2
+                    && polyline[0] == polyline[polyline.len() - 1]
+                    && chunk.links(polyline[0]).1 == 2;
+                let vertices = &polyline[..polyline.len() - closed as usize];
+                for &p in vertices {
+                    *visits.entry(p).or_default() += 1;
+                }
+            }
+
+            // the line pixels are visited once, and the nodes once for each
+            // line ending there; isolated pixels have no lines
+            for i in y..y + h {
+                for j in x..x + w {
+                    let p = [j, i];
+                    let expected = match chunk.is_set(j as isize, i as isize) {
+                        false => 0,
+                        true => match chunk.links(p).1 {
+                            2 => 1,
+                            n => n,
+                        },
+                    };
+                    let visited = visits.remove(&p).unwrap_or(0);
+                    assert_eq!(visited, expected, "pixel {p:?}, case {case}");
+                }
+            }
+            assert!(visits.is_empty(), "outside of the chunk: {visits:?}");
+        }
+    }
+}
@@ -0,0 +1,381 @@
+use indicatif::ProgressBar;
+
+use crate::{
+    bitslice,
+    region::{Border, Region},
+    tiles::{self, Rect},
+};
+
+//================================
+// RASTER SKELETONIZATION
+//================================
+// Binary image thinning (skeletonization) in-place.
+// Implements Zhang-Suen algorithm.
+// http://agcggs680.pbworks.com/f/Zhan-Suen_algorithm.pdf
+// The passes over the image skip its outer ring, which is done separately,
+// looking past the edges according to the border policy of the region.
+
+/// Whether a pixel with the neighbours `p2..p9` is removed by the sub-iteration `iter`.
+pub(crate) fn removable(p: [u8; 8], iter: i32) -> bool {
+    let [p2, p3, p4, p5, p6, p7, p8, p9] = p;
+    let a: u8 = (p2 == 0 && p3 == 1) as u8
+        + (p3 == 0 && p4 == 1) as u8
+        + (p4 == 0 && p5 == 1) as u8
+        + (p5 == 0 && p6 == 1) as u8
+        + (p6 == 0 && p7 == 1) as u8
+        + (p7 == 0 && p8 == 1) as u8
+        + (p8 == 0 && p9 == 1) as u8
+        + (p9 == 0 && p2 == 1) as u8;
+    let b: u8 = p2 + p3 + p4 + p5 + p6 + p7 + p8 + p9;
+    let m1: u8 = if iter == 0 {
+        p2 * p4 * p6
+    } else {
+        p2 * p4 * p8
+    };
+    let m2: u8 = if iter == 0 {
+        p4 * p6 * p8
+    } else {
+        p2 * p6 * p8
+    };
+    a == 1 && (2..=6).contains(&b) && m1 == 0 && m2 == 0
+}
+
+/// Marks the removable pixels of the window, except on the outer ring of the
+/// image, returning whether there were any.
+#[allow(clippy::too_many_arguments)]
+pub fn thinning_zs_iteration(
+    im: &mut [u8],
+    win_x: usize,
+    win_y: usize,
+    win_w: usize,
+    win_h: usize,
+    w: usize,
+    h: usize,
+    iter: i32,
+) -> bool {
+    let mut diff: bool = false;
+    let min_x = if win_x == 0 { 1 } else { win_x };
+    let max_x = if win_x + win_w == w {
+        w - 1
+    } else {
+        win_x + win_w
+    };
+    let min_y = if win_y == 0 { 1 } else { win_y };
+    let max_y = if win_y + win_h == h {
+        h - 1
+    } else {
+        win_y + win_h
+    };
+    for i in min_y..max_y {
+        for j in min_x..max_x {
+            let p1: u8 = im[i * w + j] & 1;
+            if p1 == 0 {
+                continue;
+            }
+
+            let p2: u8 = im[(i - 1) * w + j] & 1;
+            let p3: u8 = im[(i - 1) * w + j + 1] & 1;
+            let p4: u8 = im[(i) * w + j + 1] & 1;
+            let p5: u8 = im[(i + 1) * w + j + 1] & 1;
+            let p6: u8 = im[(i + 1) * w + j] & 1;
+            let p7: u8 = im[(i + 1) * w + j - 1] & 1;
+            let p8: u8 = im[(i) * w + j - 1] & 1;
+            let p9: u8 = im[(i - 1) * w + j - 1] & 1;
+            if removable([p2, p3, p4, p5, p6, p7, p8, p9], iter) {
+                diff = true;
+                im[i * w + j] |= 2;
+            }
+        }
+    }
+
+    diff
+}
+
+/// Marks the removable pixels of `r` on the outer ring of the image, seeing
+/// the pixels past the edges through `border`.
+fn thinning_zs_border(
+    im: &mut [u8],
+    r: &Rect,
+    w: usize,
+    h: usize,
+    iter: i32,
+    border: &Border,
+) -> bool {
+    let mut diff = false;
+    let mut mark = |im: &mut [u8], i: usize, j: usize| {
+        if im[i * w + j] & 1 != 0 && removable(border.neighbours(im, w, h, j, i), iter) {
+            diff = true;
+            im[i * w + j] |= 2;
+        }
+    };
+    for i in r.y0..r.y1 {
+        if i == 0 || i == h - 1 {
+            for j in r.x0..r.x1 {
+                mark(im, i, j);
+            }
+        } else {
+            for j in [0, w - 1] {
+                if j >= r.x0 && j < r.x1 {
+                    mark(im, i, j);
+                }
+            }
+        }
+    }
+    diff
+}
+
+/// Removes the marked pixels in the window, returning the rectangle enclosing them.
+fn thinning_zs_post(
+    im: &mut [u8],
+    win_x: usize,
+    win_y: usize,
+    win_w: usize,
+    win_h: usize,
+    w: usize,
+) -> Rect {
+    let mut changed = Rect::EMPTY;
+    for i in win_y..win_y + win_h {
+        for j in win_x..win_x + win_w {
+            let marker = im[i * w + j] >> 1;
+            let old = im[i * w + j] & 1;
+            let new = old & (!marker);
+            if new != old {
+                im[i * w + j] = new;
+                changed.add(j, i);
+            }
+        }
+    }
+    changed
+}
+
+/// Thins the whole image at once, the pixels past its edges being background.
+pub fn thinning_zs(im: &mut [u8], w: usize, h: usize) {
+    let all = Rect::new(0, 0, w, h);
+    loop {
+        let mut diff = false;
+        if thinning_zs_iteration(im, 0, 0, w, h, w, h, 0)
+            | thinning_zs_border(im, &all, w, h, 0, &Border::Background)
+        {
+            diff = true;
+            thinning_zs_post(im, 0, 0, w, h, w);
+        }
+        if thinning_zs_iteration(im, 0, 0, w, h, w, h, 1)
+            | thinning_zs_border(im, &all, w, h, 1, &Border::Background)
+        {
+            diff = true;
+            thinning_zs_post(im, 0, 0, w, h, w);
+        }
+        if !diff {
+            break;
+        }
+    }
+}
+
+pub fn thinning_zs_tiled(
+    im: &mut [u8],
+    width: usize,
+    height: usize,
+    tile_width: usize,
+    tile_height: usize,
+    region: &Region,
+) {
+    let dirty = tiles::windows(width, height, tile_width, tile_height)
+        .map(|(win_x, win_y, win_w, win_h)| Rect::new(win_x, win_y, win_w, win_h))
+        .collect();
+    thinning_zs_dirty(im, width, height, tile_width, tile_height, region, dirty);
+}
+
+/// Same as `thinning_zs_tiled`, calling `progress` with the iteration, and the
+/// number of tiles done and to do in the current pass, after each tile. If it
+/// returns false, the thinning stops, leaving the image partially thinned, and
+/// false is returned.
+pub fn thinning_zs_tiled_progress(
+    im: &mut [u8],
+    width: usize,
+    height: usize,
+    tile_width: usize,
+    tile_height: usize,
+    region: &Region,
+    progress: impl FnMut(usize, usize, usize) -> bool,
+) -> bool {
+    let dirty = tiles::windows(width, height, tile_width, tile_height)
+        .map(|(win_x, win_y, win_w, win_h)| Rect::new(win_x, win_y, win_w, win_h))
+        .collect();
+    thin_dirty(
+        im,
+        width,
+        height,
+        tile_width,
+        tile_height,
+        region,
+        dirty,
+        progress,
+    )
+}
+
+/// Same as `thinning_zs_tiled`, but only starting from the `dirty` rectangle of each tile.
+pub fn thinning_zs_dirty(
+    im: &mut [u8],
+    width: usize,
+    height: usize,
+    tile_width: usize,
+    tile_height: usize,
+    region: &Region,
+    dirty: Vec<Rect>,
+) {
+    thin_dirty(
+        im,
+        width,
+        height,
+        tile_width,
+        tile_height,
+        region,
+        dirty,
+        |_, _, _| true,
+    );
+}
+
+#[allow(clippy::too_many_arguments)]
+fn thin_dirty(
+    im: &mut [u8],
+    width: usize,
+    height: usize,
+    tile_width: usize,
+    tile_height: usize,
+    region: &Region,
+    dirty: Vec<Rect>,
+    mut progress: impl FnMut(usize, usize, usize) -> bool,
+) -> bool {
+    let ntx = width.div_ceil(tile_width);
+    let nty = height.div_ceil(tile_height);
+    let total_tiles = ntx * nty;
+
+    // Whether a pixel gets removed only depends on its 3x3 neighbourhood, so a
+    // pixel that was kept by a sub-iteration stays that way until one of its
+    // neighbours is removed. For each sub-iteration and tile, we only keep the
+    // rectangle of the pixels that might have changed since they were last
+    // checked: the removed pixels and their one-pixel halo, which can spill into
+    // the neighbouring tiles. Pixels outside `region` are never checked.
+    let windows = tiles::windows(width, height, tile_width, tile_height)
+        .map(|(win_x, win_y, win_w, win_h)| {
+            Rect::new(win_x, win_y, win_w, win_h).intersect(&region.rect)
+        })
+        .collect::<Vec<_>>();
+    let dirty = dirty
+        .iter()
+        .zip(&windows)
+        .map(|(d, w)| d.intersect(w))
+        .collect::<Vec<_>>();
+    let mut dirty = [dirty.clone(), dirty];
+
+    let mut iter = 1;
+    loop {
+        let remaining_tiles = (0..total_tiles)
+            .filter(|&t| !dirty[0][t].is_empty() || !dirty[1][t].is_empty())
+            .count();
+        if remaining_tiles == 0 {
+            return true;
+        }
+        let remaining_pixels = dirty
+            .iter()
+            .flatten()
+            .map(|r| r.width() * r.height())
+            .sum::<usize>();
+        log::info!(
+            "Starting iteration {iter}, {remaining_tiles}/{total_tiles}, {remaining_pixels} pixels"
+        );
+
+        for (sub, name) in ["H", "V"].iter().enumerate() {
+            let remaining_tiles = dirty[sub].iter().filter(|r| !r.is_empty()).count();
+            let pb = ProgressBar::new(remaining_tiles as u64)
+                .with_message(format!("Starting thinning {name}"));
+            log::info!("Starting thinning {name}");
+            let mut marked = vec![Rect::EMPTY; total_tiles];
+            let mut done = 0;
+            for tile in 0..total_tiles {
+                let r = dirty[sub][tile];
+                if r.is_empty() {
+                    continue;
+                }
+                let inner = bitslice::thinning_zs_iteration(
+                    im,
+                    r.x0,
+                    r.y0,
+                    r.width(),
+                    r.height(),
+                    width,
+                    height,
+                    sub as i32,
+                );
+                let outer = thinning_zs_border(im, &r, width, height, sub as i32, &region.border);
+                if inner || outer {
+                    region.unmark_outside(im, width, &r);
+                    marked[tile] = r;
+                }
+                dirty[sub][tile] = Rect::EMPTY;
+                pb.inc(1);
+                done += 1;
+                if !progress(iter, done, remaining_tiles) {
+                    // drop the removal markers, the pixels are left as they were
+                    for r in marked.iter().filter(|r| !r.is_empty()) {
+                        for i in r.y0..r.y1 {
+                            for p in &mut im[i * width + r.x0..i * width + r.x1] {
+                                *p &= 1;
+                            }
+                        }
+                    }
+                    pb.abandon();
+                    log::info!("Thinning cancelled");
+                    return false;
+                }
+            }
+            pb.finish();
+
+            let remaining_tiles = marked.iter().filter(|r| !r.is_empty()).count();
+            let pb = ProgressBar::new(remaining_tiles as u64)
+                .with_message(format!("Starting pixel removal {name}"));
+            log::info!("Starting pixel removal {name}");
+            for r in marked.iter().filter(|r| !r.is_empty()) {
+                let removed = thinning_zs_post(im, r.x0, r.y0, r.width(), r.height(), width);
+                let halo = removed.expand(1, width, height);
+                if !halo.is_empty() {
+                    for ti_y in halo.y0 / tile_height..=(halo.y1 - 1) / tile_height {
+                        for ti_x in halo.x0 / tile_width..=(halo.x1 - 1) / tile_width {
+                            let tile = ti_y * ntx + ti_x;
+                            let spill = halo.intersect(&windows[tile]);
+                            for d in &mut dirty {
+                                d[tile] = d[tile].union(&spill);
+                            }
+                        }
+                    }
+                }
+                pb.inc(1);
+            }
+            pb.finish();
+        }
+
+        iter += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{random_mask, Rng};
+
+    #[test]
+    fn tiled_matches_whole_image() {
+        let mut rng = Rng(0x853c_49e6_748f_ea9b);
+        for _ in 0..200 {
+            let (w, h) = (1 + rng.below(40), 1 + rng.below(40));
+            let im = random_mask(&mut rng, w, h);
+            let mut expected = im.clone();
+            thinning_zs(&mut expected, w, h);
+            for (tw, th) in [(1, 1), (2, 3), (5, 4), (8, 8), (16, 7), (w, h)] {
+                let mut actual = im.clone();
+                thinning_zs_tiled(&mut actual, w, h, tw, th, &Region::full(w, h));
+                assert_eq!(actual, expected, "{w}x{h}, tiles {tw}x{th}");
+            }
+        }
+    }
+}
@@ -0,0 +1,203 @@
+//! Runs the command-line tool on the small rasters in `tests/data`, and compares
+//! its outputs with the expected ones there. Set `UPDATE_EXPECTED=1` to
+//! overwrite the expected outputs with the current ones instead, after checking
+//! that the changes are intended.
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use gdal::{raster::GdalType, vector::LayerAccess, Dataset};
+
+fn data(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/data")
+        .join(name)
+}
+
+/// An empty directory for the files of `test`.
+fn scratch_dir(test: &str) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(test);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Runs the tool on a copy of `input` in `dir`, as it works in place, returning
+/// the path of the copy.
+fn run(dir: &Path, input: &str, args: &[&str]) -> PathBuf {
+    let path = dir.join(input);
+    fs::copy(data(input), &path).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_thinning"))
+        .arg(&path)
+        .args(args)
+        .current_dir(dir)
+        .env("RUST_LOG", "warn")
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    path
+}
+
+/// Replaces the expected output with `actual` when updating them.
+fn update_expected(actual: &Path, expected: &str) -> bool {
+    if env::var_os("UPDATE_EXPECTED").is_none() {
+        return false;
+    }
+    fs::copy(actual, data(expected)).unwrap();
+    true
+}
+
+struct Raster<T> {
+    size: (usize, usize),
+    block_size: (usize, usize),
+    geo_transform: Option<[f64; 6]>,
+    pixels: Vec<T>,
+}
+
+fn read_raster<T: GdalType + Copy + Default>(path: &Path) -> Raster<T> {
+    let ds = Dataset::open(path).unwrap();
+    let band = ds.rasterband(1).unwrap();
+    let size = band.size();
+    let mut pixels = vec![T::default(); size.0 * size.1];
+    band.read_into_slice((0, 0), size, size, &mut pixels, None)
+        .unwrap();
+    Raster {
+        size,
+        block_size: band.block_size(),
+        geo_transform: ds.geo_transform().ok(),
+        pixels,
+    }
+}
+
+fn assert_raster<T: GdalType + Copy + Default + PartialEq + std::fmt::Debug>(
+    actual: &Path,
+    expected: &str,
+) {
+    if update_expected(actual, expected) {
+        return;
+    }
+    let actual = read_raster::<T>(actual);
+    let expected = read_raster::<T>(&data(expected));
+    assert_eq!(actual.size, expected.size);
+    assert_eq!(actual.block_size, expected.block_size);
+    assert_eq!(actual.geo_transform, expected.geo_transform);
+    let width = actual.size.0;
+    for (i, (a, e)) in actual.pixels.iter().zip(&expected.pixels).enumerate() {
+        assert_eq!(a, e, "pixel {}, {}", i % width, i / width);
+    }
+}
+
+/// The vertices of the polylines of the first layer, and the EPSG code of its CRS.
+fn read_polylines(path: &Path) -> (Vec<Vec<(f64, f64)>>, Option<i32>) {
+    let ds = Dataset::open(path).unwrap();
+    let mut layer = ds.layer(0).unwrap();
+    let epsg = layer.spatial_ref().and_then(|srs| srs.auth_code().ok());
+    let polylines = layer
+        .features()
+        .map(|feature| {
+            feature
+                .geometry()
+                .unwrap()
+                .get_point_vec()
+                .into_iter()
+                .map(|(x, y, _)| (x, y))
+                .collect()
+        })
+        .collect();
+    (polylines, epsg)
+}
+
+fn assert_polylines(actual: &Path, expected: &str) {
+    if update_expected(actual, expected) {
+        return;
+    }
+    let (actual, actual_epsg) = read_polylines(actual);
+    let (expected, expected_epsg) = read_polylines(&data(expected));
+    assert_eq!(actual_epsg, expected_epsg);
+    assert_eq!(actual.len(), expected.len());
+    for (k, (a, e)) in actual.iter().zip(&expected).enumerate() {
+        assert_eq!(a.len(), e.len(), "polyline {k}");
+        for (p, q) in a.iter().zip(e) {
+            assert!(
+                (p.0 - q.0).abs() < 1e-6 && (p.1 - q.1).abs() < 1e-6,
+                "polyline {k}: {p:?} != {q:?}"
+            );
+        }
+    }
+}
+
+#[test]
+fn thins_striped() {
+    let dir = scratch_dir("thins_striped");
+    let output = run(&dir, "striped.tif", &[]);
+    assert_raster::<u8>(&output, "striped_thinned.tif");
+}
+
+#[test]
+fn thins_tiled() {
+    let dir = scratch_dir("thins_tiled");
+    let output = run(&dir, "tiled.tif", &[]);
+    assert_raster::<u8>(&output, "tiled_thinned.tif");
+}
+
+#[test]
+fn thins_uint16_keeping_values() {
+    let dir = scratch_dir("thins_uint16_keeping_values");
+    let output = run(&dir, "classes.tif", &[]);
+    assert_raster::<u16>(&output, "classes_thinned.tif");
+}
+
+#[test]
+fn traces_in_pixels() {
+    let dir = scratch_dir("traces_in_pixels");
+    let trace = dir.join("skeleton.geojson");
+    run(&dir, "striped.tif", &["--trace", trace.to_str().unwrap()]);
+    assert_polylines(&trace, "striped.geojson");
+}
+
+#[test]
+fn traces_in_map_coordinates() {
+    let dir = scratch_dir("traces_in_map_coordinates");
+    let trace = dir.join("skeleton.geojson");
+    run(
+        &dir,
+        "tiled.tif",
+        &["--trace", trace.to_str().unwrap(), "--georef"],
+    );
+    assert_polylines(&trace, "tiled_georef.geojson");
+}
+
+#[test]
+fn traces_exactly() {
+    let dir = scratch_dir("traces_exactly");
+    let trace = dir.join("skeleton.geojson");
+    run(
+        &dir,
+        "tiled.tif",
+        &["--trace", trace.to_str().unwrap(), "--tracer", "exact"],
+    );
+    assert_polylines(&trace, "tiled_exact.geojson");
+}
+
+#[test]
+fn thins_at_the_edges() {
+    let dir = scratch_dir("thins_at_the_edges");
+    let output = run(&dir, "edges.tif", &[]);
+    assert_raster::<u8>(&output, "edges_thinned.tif");
+}
+
+/// The block in the top-right corner disappears, unlike with the background
+/// border, as the copies of its pixels past the corner are thinned with it.
+#[test]
+fn thins_replicating_the_edges() {
+    let dir = scratch_dir("thins_replicating_the_edges");
+    let output = run(&dir, "edges.tif", &["--border", "replicate"]);
+    assert_raster::<u8>(&output, "edges_replicate.tif");
+}
@@ -0,0 +1,92 @@
+use std::fmt::Write;
+
+use thinning_core::{prune, region::Region, rings, skeleton};
+use wasm_bindgen::prelude::*;
+
+//================================
+// WEBASSEMBLY BINDINGS
+//================================
+// Masks are `Uint8Array`s of `width * height` bytes, row by row, where only the
+// lowest bit of a pixel is part of the mask, e.g. 0/255 from a canvas. They're
+// small enough to be processed in a single tile. The core still makes progress
+// bars, which need a clock that works in the browser, hence indicatif 0.17.9 or
+// later; they're never drawn, as there's no terminal.
+
+fn check_size(mask: &[u8], width: usize, height: usize) -> Result<(), JsError> {
+    if width == 0 || height == 0 || mask.len() != width * height {
+        return Err(JsError::new(&format!(
+            "expected {width}x{height} pixels, got {}",
+            mask.len()
+        )));
+    }
+    Ok(())
+}
+
+/// Thins the mask in place, leaving it with 0 and 1, then removes the spurs of
+/// at most `max_spur_length` pixels, if given.
+#[wasm_bindgen]
+pub fn thin(
+    mask: &mut [u8],
+    width: usize,
+    height: usize,
+    max_spur_length: Option<usize>,
+) -> Result<(), JsError> {
+    check_size(mask, width, height)?;
+    for p in mask.iter_mut() {
+        *p &= 1;
+    }
+    let region = Region::full(width, height);
+    thinning_core::thinning_zs_tiled(mask, width, height, width, height, &region);
+    if let Some(max_length) = max_spur_length {
+        prune::prune_spurs(mask, width, height, width, height, &region, max_length);
+    }
+    Ok(())
+}
+
+/// Traces a thinned mask into a GeoJSON `FeatureCollection` of `LineString`s,
+/// in pixel coordinates, with a `ring` property telling whether they're closed loops.
+#[wasm_bindgen]
+pub fn trace(mask: &[u8], width: usize, height: usize) -> Result<String, JsError> {
+    check_size(mask, width, height)?;
+    let im = mask.iter().map(|&p| p & 1).collect::<Vec<_>>();
+    let polylines = skeleton::trace_skeleton(&im, width, height, 0, 0, width, height, 10, 999);
+    let (polylines, rings) = rings::close_rings(polylines);
+    Ok(to_geojson(&polylines, &rings))
+}
+
+/// Thins a copy of the mask and traces it, see `thin` and `trace`.
+#[wasm_bindgen]
+pub fn skeletonize(
+    mask: &[u8],
+    width: usize,
+    height: usize,
+    max_spur_length: Option<usize>,
+) -> Result<String, JsError> {
+    let mut im = mask.to_vec();
+    thin(&mut im, width, height, max_spur_length)?;
+    trace(&im, width, height)
+}
+
+fn to_geojson(polylines: &[Vec<[usize; 2]>], rings: &[bool]) -> String {
+    let mut json = String::from(r#"{"type":"FeatureCollection","features":["#);
+    for (k, polyline) in polylines.iter().enumerate() {
+        if k > 0 {
+            json.push(',');
+        }
+        write!(
+            json,
+            r#"{{"type":"Feature","properties":{{"ring":{}}},"geometry":{{"type":"LineString","coordinates":["#,
+            rings[k]
+        )
+        .unwrap();
+        for (v, [x, y]) in polyline.iter().enumerate() {
+            if v > 0 {
+                json.push(',');
+            }
+            write!(json, "[{x},{y}]").unwrap();
+        }
+        json.push_str("]}}");
+    }
+    json.push_str("]}");
+    json
+}
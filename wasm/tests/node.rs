@@ -0,0 +1,64 @@
+use thinning_core::{prune, region::Region};
+use wasm_bindgen_test::wasm_bindgen_test;
+
+fn rectangle(width: usize, height: usize, value: u8) -> Vec<u8> {
+    let mut mask = vec![0; width * height];
+    for i in 5..15 {
+        for j in 3..37 {
+            mask[i * width + j] = value;
+        }
+    }
+    mask
+}
+
+#[wasm_bindgen_test]
+fn thins_in_place() {
+    let (width, height) = (40, 20);
+    let mut mask = rectangle(width, height, 1);
+    thinning::thin(&mut mask, width, height, None).unwrap();
+    let count = mask.iter().filter(|&&p| p != 0).count();
+    assert!(count > 0 && count < 40);
+
+    let mut expected = rectangle(width, height, 1);
+    thinning_core::thinning_zs(&mut expected, width, height);
+    assert_eq!(mask, expected);
+}
+
+#[wasm_bindgen_test]
+fn thins_and_prunes() {
+    let (width, height) = (40, 20);
+    let mut mask = rectangle(width, height, 1);
+    thinning::thin(&mut mask, width, height, Some(5)).unwrap();
+
+    let mut expected = rectangle(width, height, 1);
+    thinning_core::thinning_zs(&mut expected, width, height);
+    let region = Region::full(width, height);
+    prune::prune_spurs(&mut expected, width, height, width, height, &region, 5);
+    assert_eq!(mask, expected);
+}
+
+#[wasm_bindgen_test]
+fn thins_0_255_masks() {
+    let (width, height) = (40, 20);
+    let mut expected = rectangle(width, height, 1);
+    thinning::thin(&mut expected, width, height, None).unwrap();
+    let mut mask = rectangle(width, height, 255);
+    thinning::thin(&mut mask, width, height, None).unwrap();
+    assert_eq!(mask, expected);
+    let json = thinning::skeletonize(&rectangle(width, height, 255), width, height, None).unwrap();
+    assert!(json.contains(r#""type":"LineString""#));
+}
+
+#[wasm_bindgen_test]
+fn traces_to_geojson() {
+    let (width, height) = (40, 20);
+    let json = thinning::skeletonize(&rectangle(width, height, 1), width, height, Some(5)).unwrap();
+    assert!(json.starts_with(r#"{"type":"FeatureCollection","features":[{"type":"Feature""#));
+    assert!(json.contains(r#""type":"LineString""#));
+}
+
+#[wasm_bindgen_test]
+fn traces_empty_mask() {
+    let json = thinning::trace(&[0; 12], 4, 3).unwrap();
+    assert_eq!(json, r#"{"type":"FeatureCollection","features":[]}"#);
+}